@@ -1,7 +1,12 @@
+use patch::{pointer_add, pointer_get_mut, pointer_remove};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use errors::*;
 use std::str::FromStr;
+use std::ops::{Deref, DerefMut};
+use std::fmt;
 use std;
 
 /// Permitted JSON-API values (all JSON Values)
@@ -11,7 +16,6 @@ pub type JsonApiValue = serde_json::Value;
 pub type Resources = Vec<Resource>;
 /// Vector of `ResourceIdentifiers`
 pub type ResourceIdentifiers = Vec<ResourceIdentifier>;
-pub type Links = HashMap<String, JsonApiValue>;
 /// Meta-data object, can contain any data
 pub type Meta = HashMap<String, JsonApiValue>;
 /// Resource Attributes, can be any JSON value
@@ -27,11 +31,19 @@ pub type JsonApiId = String;
 pub type JsonApiIds<'a> = Vec<&'a JsonApiId>;
 
 /// Resource Identifier
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+///
+/// Identifies a resource by `id`, or by a local id (`lid`) for resources that have not been
+/// assigned an `id` by the server yet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct ResourceIdentifier {
     #[serde(rename = "type")]
     pub _type: String,
-    pub id: JsonApiId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<JsonApiId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
 }
 
 /// JSON-API Resource
@@ -39,7 +51,11 @@ pub struct ResourceIdentifier {
 pub struct Resource {
     #[serde(rename = "type")]
     pub _type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<JsonApiId>,
+    /// Local id, identifying a resource created in the same request document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lid: Option<String>,
     #[serde(default)]
     pub attributes: ResourceAttributes,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -51,11 +67,27 @@ pub struct Resource {
 }
 
 /// Relationship with another object
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+///
+/// The specification requires at least one of `links`, `data` or `meta`. A missing `data` member
+/// is `None`, while `"data": null` is `Some(IdentifierData::None)`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Relationship {
-    pub data: IdentifierData,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<IdentifierData>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub links: Option<Links>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+}
+
+/// Deserialize a member that is present (including `null`) as `Some`, so that it can be told
+/// apart from an absent member with `#[serde(default)]`
+pub(crate) fn deserialize_present<'de, T, D>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+    where T: Deserialize<'de>,
+          D: Deserializer<'de>
+{
+    T::deserialize(deserializer).map(Some)
 }
 
 /// Valid data Resource (can be None)
@@ -123,12 +155,79 @@ pub struct JsonApiError {
 }
 
 /// Optional `JsonApiDocument` payload identifying the JSON-API version the server implements
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// and the extensions and profiles applied to the document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct JsonApiInfo {
     pub version: Option<String>,
+    /// URIs of the applied extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ext: Option<Vec<String>>,
+    /// URIs of the applied profiles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<Vec<String>>,
     pub meta: Option<Meta>,
 }
 
+/// Map of link names to `Link`s
+///
+/// Dereferences to the underlying `HashMap`, and provides accessors for the link names used by
+/// the specification.
+///
+/// ```
+/// # extern crate jsonapi;
+/// # extern crate serde_json;
+/// use jsonapi::api::Links;
+///
+/// # fn main() {
+/// let links: Links = serde_json::from_str(r#"{
+///   "self": "http://example.com/articles/1",
+///   "related": {
+///     "href": "http://example.com/articles/1/comments",
+///     "meta": { "count": 10 }
+///   }
+/// }"#).unwrap();
+///
+/// assert_eq!(links.self_link().and_then(|l| l.href()), Some("http://example.com/articles/1"));
+/// assert_eq!(links.related().and_then(|l| l.href()),
+///            Some("http://example.com/articles/1/comments"));
+/// assert!(links.next().is_none());
+/// # }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Links(pub HashMap<String, Link>);
+
+/// A link, either a bare URL string or a link object
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Link {
+    None,
+    Href(String),
+    Object(LinkObject),
+}
+
+/// Link object, as an alternative to a bare URL string
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LinkObject {
+    pub href: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rel: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub describedby: Option<Box<Link>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _type: Option<String>,
+    /// A language tag or an array of language tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hreflang: Option<JsonApiValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    /// Other members, such as those of extensions, kept as they are
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, JsonApiValue>,
+}
+
 /// Pagination links
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Pagination {
@@ -138,16 +237,21 @@ pub struct Pagination {
     pub last: Option<String>,
 }
 
-
-#[derive(Debug)]
+/// A single change to a resource, as produced by `Resource::diff`
+///
+/// The `subject` is an attribute name or JSON-pointer-style path (`settings/theme`), or a
+/// relationship name, optionally followed by `/-` (append) or `/<index>`. `previous` and
+/// `next` are `null` where there is no value.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Patch {
     pub patch_type: PatchType,
+    pub operation: PatchOperation,
     pub subject: String,
     pub previous: JsonApiValue,
     pub next: JsonApiValue,
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PatchSet {
     pub resource_type: String,
     pub resource_id: String,
@@ -155,32 +259,209 @@ pub struct PatchSet {
 }
 
 impl PatchSet {
+    /// An empty patch set for a resource
+    ///
+    /// Panics if the resource has no id, see `try_new_for`
     pub fn new_for(resource: &Resource) -> Self {
-        PatchSet {
-            resource_type: resource._type.clone(),
-            resource_id: resource.id.clone().expect("You need the Id to patch"),
-            patches: Vec::<Patch>::new(),
+        Self::try_new_for(resource).expect("You need the Id to patch")
+    }
+
+    /// An empty patch set for a resource, failing with `Error::MissingId` if it has no id
+    pub fn try_new_for(resource: &Resource) -> Result<Self> {
+        match resource.id {
+            Some(ref id) => Ok(PatchSet {
+                resource_type: resource._type.clone(),
+                resource_id: id.clone(),
+                patches: Vec::<Patch>::new(),
+            }),
+            None => Err(Error::MissingId { resource_type: resource._type.clone() }),
         }
     }
 
-    pub fn push(&mut self, patch: Patch) -> () {
+    pub fn push(&mut self, patch: Patch) {
         self.patches.push(patch);
     }
+
+    /// The patches undoing this patch set, to apply to the patched resource
+    ///
+    /// Each patch is inverted with `Patch::invert`, in reverse order.
+    ///
+    /// ```
+    /// use jsonapi::api::Resource;
+    /// use std::str::FromStr;
+    ///
+    /// let before = Resource::from_str(r#"{
+    ///   "type": "articles", "id": "1",
+    ///   "attributes": { "title": "Rails is Omakase", "tags": ["rails", "ruby"] }
+    /// }"#).unwrap();
+    /// let mut after = before.clone();
+    /// after.set_attribute("title", "Rails was Omakase").unwrap();
+    /// after.set_attribute("tags", vec!["rails", "ruby", "opinion"]).unwrap();
+    ///
+    /// let patchset = before.diff_deep(after.clone()).unwrap();
+    /// assert_eq!(after.patch_strict(patchset.invert()).unwrap(), before);
+    /// ```
+    pub fn invert(&self) -> PatchSet {
+        PatchSet {
+            resource_type: self.resource_type.clone(),
+            resource_id: self.resource_id.clone(),
+            patches: self.patches.iter().rev().map(Patch::invert).collect(),
+        }
+    }
+}
+
+impl Links {
+    pub fn new() -> Self {
+        Links(HashMap::new())
+    }
+
+    /// Get a link by name, treating a `null` link as absent
+    pub fn get_link(&self, name: &str) -> Option<&Link> {
+        match self.0.get(name) {
+            None | Some(&Link::None) => None,
+            Some(link) => Some(link),
+        }
+    }
+
+    /// The `self` link
+    pub fn self_link(&self) -> Option<&Link> {
+        self.get_link("self")
+    }
+
+    pub fn related(&self) -> Option<&Link> {
+        self.get_link("related")
+    }
+
+    pub fn describedby(&self) -> Option<&Link> {
+        self.get_link("describedby")
+    }
+
+    pub fn first(&self) -> Option<&Link> {
+        self.get_link("first")
+    }
+
+    pub fn prev(&self) -> Option<&Link> {
+        self.get_link("prev")
+    }
+
+    pub fn next(&self) -> Option<&Link> {
+        self.get_link("next")
+    }
+
+    pub fn last(&self) -> Option<&Link> {
+        self.get_link("last")
+    }
+
+    /// Collect the pagination links, using the `href` of each
+    pub fn pagination(&self) -> Pagination {
+        let href = |link: Option<&Link>| link.and_then(|l| l.href()).map(|s| s.to_string());
+        Pagination {
+            first: href(self.first()),
+            prev: href(self.prev()),
+            next: href(self.next()),
+            last: href(self.last()),
+        }
+    }
+}
+
+impl Deref for Links {
+    type Target = HashMap<String, Link>;
+
+    fn deref(&self) -> &HashMap<String, Link> {
+        &self.0
+    }
+}
+
+impl DerefMut for Links {
+    fn deref_mut(&mut self) -> &mut HashMap<String, Link> {
+        &mut self.0
+    }
+}
+
+impl From<HashMap<String, Link>> for Links {
+    fn from(map: HashMap<String, Link>) -> Self {
+        Links(map)
+    }
+}
+
+impl Link {
+    /// The URL of this link, `None` for a `null` link
+    pub fn href(&self) -> Option<&str> {
+        match *self {
+            Link::None => None,
+            Link::Href(ref href) => Some(href),
+            Link::Object(ref object) => Some(&object.href),
+        }
+    }
+
+    /// The link object `meta`, only present on link objects
+    pub fn meta(&self) -> Option<&Meta> {
+        match *self {
+            Link::Object(ref object) => object.meta.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&LinkObject> {
+        match *self {
+            Link::Object(ref object) => Some(object),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for Link {
+    fn from(href: String) -> Self {
+        Link::Href(href)
+    }
+}
+
+impl<'a> From<&'a str> for Link {
+    fn from(href: &'a str) -> Self {
+        Link::Href(href.into())
+    }
+}
+
+impl From<LinkObject> for Link {
+    fn from(object: LinkObject) -> Self {
+        Link::Object(object)
+    }
 }
 
 /// Top-level JSON-API Document
 impl JsonApiDocument {
     fn has_errors(&self) -> bool {
-        !self.errors.is_none()
+        self.errors.is_some()
     }
     fn has_meta(&self) -> bool {
-        !self.errors.is_none()
+        self.meta.is_some()
     }
     fn has_included(&self) -> bool {
-        !self.included.is_none()
+        self.included.is_some()
     }
     fn has_data(&self) -> bool {
-        !self.data.is_none()
+        self.data.is_some()
+    }
+    /// All resources in `data` and `included`, each with the JSON pointer locating it
+    fn resources_with_pointers(&self) -> Vec<(String, &Resource)> {
+        let mut resources = Vec::new();
+        match self.data {
+            Some(PrimaryData::Single(ref resource)) => {
+                resources.push(("/data".to_string(), &**resource))
+            }
+            Some(PrimaryData::Multiple(ref data)) => {
+                for (index, resource) in data.iter().enumerate() {
+                    resources.push((format!("/data/{}", index), resource));
+                }
+            }
+            Some(PrimaryData::None) | None => {}
+        }
+        if let Some(ref included) = self.included {
+            for (index, resource) in included.iter().enumerate() {
+                resources.push((format!("/included/{}", index), resource));
+            }
+        }
+        resources
     }
     /// This function returns `false` if the `JsonApiDocument` contains any violations of the
     /// specification. See `DocumentValidationError`
@@ -200,10 +481,7 @@ impl JsonApiDocument {
     /// assert_eq!(doc.is_valid(), false);
     /// ```
     pub fn is_valid(&self) -> bool {
-        match self.validate() {
-            Some(_) => false,
-            None => true,
-        }
+        self.validate().is_none()
     }
 
     /// This function returns a `Vec` with identified specification violations enumerated in
@@ -245,12 +523,65 @@ impl JsonApiDocument {
             errors.push(DocumentValidationError::MissingContent);
         }
 
+        let mut identities = HashSet::new();
+        let mut local_identities = HashSet::new();
+        for (pointer, resource) in self.resources_with_pointers() {
+            resource.validate_at(&pointer, &mut errors);
+            if let Some(ref id) = resource.id {
+                if !identities.insert((&resource._type, id)) {
+                    errors.push(DocumentValidationError::DuplicateResource(pointer.clone()));
+                }
+            }
+            if let Some(ref lid) = resource.lid {
+                if !local_identities.insert((&resource._type, lid)) {
+                    errors.push(DocumentValidationError::DuplicateLocalId(pointer));
+                }
+            }
+        }
+
+        if let Some(ref jsonapi) = self.jsonapi {
+            jsonapi.validate_at("/jsonapi", &mut errors);
+        }
+
         match errors.len() {
             0 => None,
             _ => Some(errors),
         }
 
     }
+
+    /// Like `validate`, but also requires every resource to have an `id`, as resources in a
+    /// server response must
+    ///
+    /// ```
+    /// use jsonapi::api::{JsonApiDocument, PrimaryData, Resource, DocumentValidationError};
+    ///
+    /// let doc = JsonApiDocument {
+    ///     data: Some(PrimaryData::Single(Box::new(Resource {
+    ///         _type: "articles".into(),
+    ///         ..Default::default()
+    ///     }))),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// assert!(doc.validate().is_none());
+    /// assert_eq!(doc.validate_response(),
+    ///            Some(vec![DocumentValidationError::MissingResourceId("/data".into())]));
+    /// ```
+    pub fn validate_response(&self) -> Option<Vec<DocumentValidationError>> {
+        let mut errors = self.validate().unwrap_or_default();
+
+        for (pointer, resource) in self.resources_with_pointers() {
+            if resource.id.is_none() {
+                errors.push(DocumentValidationError::MissingResourceId(pointer));
+            }
+        }
+
+        match errors.len() {
+            0 => None,
+            _ => Some(errors),
+        }
+    }
 }
 
 impl FromStr for JsonApiDocument {
@@ -273,11 +604,21 @@ impl FromStr for JsonApiDocument {
     /// assert_eq!(doc.is_ok(), true);
     /// ```
     fn from_str(s: &str) -> Result<Self> {
-        serde_json::from_str(s).chain_err(|| "Error parsing Document")
+        Ok(serde_json::from_str(s)?)
     }
 }
 
 impl Resource {
+    /// The `ResourceIdentifier` for this resource, carrying its `id` and `lid`
+    pub fn as_identifier(&self) -> ResourceIdentifier {
+        ResourceIdentifier {
+            _type: self._type.clone(),
+            id: self.id.clone(),
+            lid: self.lid.clone(),
+            meta: None,
+        }
+    }
+
     pub fn get_relationship(&self, name: &str) -> Option<&Relationship> {
         match self.relationships {
             None => None,
@@ -330,63 +671,835 @@ impl Resource {
         }
     }
 
+    /// Get an attribute `JsonApiValue` by a dot-separated path into nested objects, such as
+    /// `address.city`
+    pub fn get_attribute_path(&self, path: &str) -> Option<&JsonApiValue> {
+        let mut segments = path.split('.');
+        let mut value = self.attributes.get(segments.next().unwrap_or(""))?;
+        for segment in segments {
+            value = value.as_object()?.get(segment)?;
+        }
+        Some(value)
+    }
+
+    /// Deserialize an attribute, given by name or by a dot-separated path into nested objects
+    ///
+    /// ```
+    /// use jsonapi::api::{JsonApiDataError, Resource};
+    /// use std::str::FromStr;
+    ///
+    /// let resource = Resource::from_str(r#"{
+    ///   "id": "9",
+    ///   "type": "people",
+    ///   "attributes": {
+    ///     "name": "Dan",
+    ///     "address": { "city": "Amsterdam" }
+    ///   }
+    /// }"#).unwrap();
+    ///
+    /// let city: String = resource.get_attribute_as("address.city").unwrap();
+    /// assert_eq!(city, "Amsterdam");
+    /// assert_eq!(resource.get_attribute_as::<u32>("name"),
+    ///            Err(JsonApiDataError::IncompatibleAttributeType("name".into())));
+    /// ```
+    pub fn get_attribute_as<T: DeserializeOwned>(&self, path: &str)
+        -> std::result::Result<T, JsonApiDataError>
+    {
+        let value = self.get_attribute_path(path)
+            .ok_or_else(|| JsonApiDataError::AttributeNotFound(path.into()))?;
+        T::deserialize(value)
+            .map_err(|_| JsonApiDataError::IncompatibleAttributeType(path.into()))
+    }
+
+    /// Serialize a value into an attribute, given by name or by a dot-separated path into nested
+    /// objects. Missing objects along the path, or `null` values, are replaced by objects.
+    pub fn set_attribute<T: Serialize>(&mut self, path: &str, value: T)
+        -> std::result::Result<(), JsonApiDataError>
+    {
+        let value = serde_json::to_value(value)
+            .map_err(|_| JsonApiDataError::IncompatibleAttributeType(path.into()))?;
+
+        let mut segments = path.split('.');
+        let first = segments.next().unwrap_or("");
+        let mut target = self.attributes
+            .entry(first.to_string())
+            .or_insert(JsonApiValue::Null);
+        let mut traversed = first.to_string();
+
+        for segment in segments {
+            if target.is_null() {
+                *target = JsonApiValue::Object(Default::default());
+            }
+            target = match *target {
+                JsonApiValue::Object(ref mut object) => {
+                    object.entry(segment).or_insert(JsonApiValue::Null)
+                }
+                _ => return Err(JsonApiDataError::IncompatibleAttributeType(traversed)),
+            };
+            traversed.push('.');
+            traversed.push_str(segment);
+        }
+
+        *target = value;
+        Ok(())
+    }
+
+    /// The changes that turn this resource into `other`, for attributes and relationships
+    ///
+    /// To-many relationships are compared member by member, removals (highest index first) before
+    /// additions, and replaced when reordered. Relationship `links` and `meta` are not compared.
+    ///
+    /// ```
+    /// use jsonapi::api::{PatchOperation, PatchType, Resource};
+    /// use std::str::FromStr;
+    ///
+    /// let before = Resource::from_str(r#"{
+    ///   "type": "articles", "id": "1",
+    ///   "attributes": { "title": "Rails is Omakase" },
+    ///   "relationships": { "tags": { "data": [{ "type": "tags", "id": "1" }] } }
+    /// }"#).unwrap();
+    /// let after = Resource::from_str(r#"{
+    ///   "type": "articles", "id": "1",
+    ///   "attributes": { "title": "Rails is Omakase", "likes": 250 },
+    ///   "relationships": { "tags": { "data": [{ "type": "tags", "id": "2" }] } }
+    /// }"#).unwrap();
+    ///
+    /// let patchset = before.diff(after).unwrap();
+    /// let summary: Vec<(PatchType, PatchOperation, &str)> = patchset.patches
+    ///     .iter()
+    ///     .map(|p| (p.patch_type, p.operation, p.subject.as_str()))
+    ///     .collect();
+    /// assert_eq!(summary, vec![
+    ///     (PatchType::Attribute, PatchOperation::Add, "likes"),
+    ///     (PatchType::Relationship, PatchOperation::Remove, "tags/0"),
+    ///     (PatchType::Relationship, PatchOperation::Add, "tags/-"),
+    /// ]);
+    /// ```
     pub fn diff(&self, other: Resource) -> std::result::Result<PatchSet, DiffPatchError> {
+        self.diff_with(other, false)
+    }
+
+    /// Like `diff`, but recurses into attribute values that are objects or arrays, producing
+    /// patches for the nested values that changed
+    ///
+    /// Arrays are compared by index.
+    ///
+    /// ```
+    /// use jsonapi::api::Resource;
+    /// use std::str::FromStr;
+    ///
+    /// let before = Resource::from_str(r#"{
+    ///   "type": "people", "id": "9",
+    ///   "attributes": { "settings": { "theme": "dark", "language": "en" } }
+    /// }"#).unwrap();
+    /// let mut after = before.clone();
+    /// after.set_attribute("settings.theme", "light").unwrap();
+    ///
+    /// let patchset = before.diff_deep(after.clone()).unwrap();
+    /// assert_eq!(patchset.patches[0].subject, "settings/theme");
+    /// assert_eq!(patchset.patches[0].next, "light");
+    ///
+    /// assert_eq!(before.clone().patch(patchset).unwrap(), after);
+    /// ```
+    pub fn diff_deep(&self, other: Resource) -> std::result::Result<PatchSet, DiffPatchError> {
+        self.diff_with(other, true)
+    }
+
+    fn diff_with(&self, other: Resource, deep: bool)
+        -> std::result::Result<PatchSet, DiffPatchError>
+    {
         if self._type != other._type {
-            Err(DiffPatchError::IncompatibleTypes(self._type.clone(), other._type.clone()))
-        } else {
+            return Err(DiffPatchError::IncompatibleTypes(self._type.clone(), other._type.clone()));
+        }
+
+        let mut patchset = PatchSet::try_new_for(self).map_err(|_| DiffPatchError::MissingId)?;
+
+        let mut names: Vec<&String> =
+            self.attributes.keys().chain(other.attributes.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let subject = pointer_segment(name);
+            match (self.attributes.get(name), other.attributes.get(name)) {
+                (Some(previous), Some(next)) if deep => {
+                    diff_values(subject, previous, next, &mut patchset)
+                }
+                (Some(previous), Some(next)) if previous != next => {
+                    patchset.push(Patch::attribute(PatchOperation::Replace, subject,
+                                                   previous.clone(), next.clone()))
+                }
+                (Some(previous), None) => {
+                    patchset.push(Patch::attribute(PatchOperation::Remove, subject,
+                                                   previous.clone(), JsonApiValue::Null))
+                }
+                (None, Some(next)) => {
+                    patchset.push(Patch::attribute(PatchOperation::Add, subject,
+                                                   JsonApiValue::Null, next.clone()))
+                }
+                _ => {}
+            }
+        }
+
+        let no_relationships = Relationships::new();
+        let self_relationships = self.relationships.as_ref().unwrap_or(&no_relationships);
+        let other_relationships = other.relationships.as_ref().unwrap_or(&no_relationships);
+
+        let mut names: Vec<&String> =
+            self_relationships.keys().chain(other_relationships.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let previous = self_relationships.get(name).map(|r| r.data.as_ref());
+            let next = other_relationships.get(name).map(|r| r.data.as_ref());
+            match (previous, next) {
+                (Some(Some(previous)), Some(Some(next))) => {
+                    diff_identifier_data(name, previous, next, &mut patchset)
+                }
+                (Some(Some(previous)), None) => {
+                    patchset.push(Patch::relationship(PatchOperation::Remove, name.clone(),
+                                                      to_value(previous), JsonApiValue::Null))
+                }
+                (None, Some(Some(next))) | (Some(None), Some(Some(next))) => {
+                    patchset.push(Patch::relationship(PatchOperation::Add, name.clone(),
+                                                      JsonApiValue::Null, to_value(next)))
+                }
+                // Removing the data of a relationship that remains can not be expressed
+                (Some(Some(_)), Some(None)) => {
+                    return Err(DiffPatchError::UnsupportedChange(name.clone()))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(patchset)
+    }
+
+    /// Apply the patches produced by `diff`, returning the patched resource
+    pub fn patch(&mut self, patchset: PatchSet) -> Result<Resource> {
+        let mut res = self.clone();
+        for patch in &patchset.patches {
+            res.apply_patch(patch)?;
+        }
+        Ok(res)
+    }
 
-            let mut self_keys: Vec<String> =
-                self.attributes.iter().map(|(key, _)| key.clone()).collect();
+    pub(crate) fn apply_patch(&mut self, patch: &Patch) -> Result<()> {
+        match patch.patch_type {
+            PatchType::Attribute => self.patch_attribute(patch),
+            PatchType::Relationship => self.patch_relationship(patch),
+        }
+    }
 
-            self_keys.sort();
+    /// `patch`, with the `-` of a removal of the last element replaced by its index
+    pub(crate) fn resolve_last_element(&self, patch: &Patch) -> Patch {
+        let mut resolved = patch.clone();
+        if patch.operation != PatchOperation::Remove || !patch.subject.ends_with("/-") {
+            return resolved;
+        }
+        let (name, path) = split_subject(&patch.subject);
+        match patch.patch_type {
+            PatchType::Attribute => {
+                if let Some(value) = self.attributes.get(&unescape_pointer_segment(name)) {
+                    resolved.subject = format!("{}{}", name, last_element_path(value, path));
+                }
+            }
+            PatchType::Relationship => {
+                if let Some(&Relationship {
+                    data: Some(IdentifierData::Multiple(ref identifiers)), ..
+                }) = self.get_relationship(name) {
+                    if !identifiers.is_empty() {
+                        resolved.subject = format!("{}/{}", name, identifiers.len() - 1);
+                    }
+                }
+            }
+        }
+        resolved
+    }
 
-            let mut other_keys: Vec<String> =
-                other.attributes.iter().map(|(key, _)| key.clone()).collect();
+    /// Apply the patches produced by `diff` or `diff_deep`, checking that the current value of
+    /// each patch's subject is still its `previous` value
+    ///
+    /// Fails with `NonExistentProperty` or `IncorrectPropertyValue` when a value is missing or
+    /// differs, in which case nothing is applied.
+    ///
+    /// ```
+    /// use jsonapi::api::{DiffPatchError, Resource};
+    /// use std::str::FromStr;
+    ///
+    /// let original = Resource::from_str(r#"{
+    ///   "type": "articles", "id": "1", "attributes": { "title": "Rails is Omakase" }
+    /// }"#).unwrap();
+    ///
+    /// let mut mine = original.clone();
+    /// mine.attributes.insert("title".into(), "Rails was Omakase".into());
+    /// let mut theirs = original.clone();
+    /// theirs.attributes.insert("title".into(), "Rails is not Omakase".into());
+    ///
+    /// let patchset = original.diff(mine.clone()).unwrap();
+    /// assert_eq!(original.patch_strict(patchset.clone()).unwrap(), mine);
+    /// assert_eq!(theirs.patch_strict(patchset),
+    ///            Err(DiffPatchError::IncorrectPropertyValue("title".into())));
+    /// ```
+    pub fn patch_strict(&self, patchset: PatchSet)
+        -> std::result::Result<Resource, DiffPatchError>
+    {
+        if self._type != patchset.resource_type {
+            return Err(DiffPatchError::IncompatibleTypes(self._type.clone(),
+                                                         patchset.resource_type.clone()));
+        }
+        match self.id {
+            None => return Err(DiffPatchError::MissingId),
+            Some(ref id) if *id != patchset.resource_id => {
+                return Err(DiffPatchError::IncompatibleIds(id.clone(),
+                                                           patchset.resource_id.clone()));
+            }
+            Some(_) => {}
+        }
 
-            other_keys.sort();
+        let mut res = self.clone();
+        for patch in &patchset.patches {
+            let current = res.current_value(patch);
+            match patch.operation {
+                PatchOperation::Add if patch.subject.ends_with("/-") => {}
+                PatchOperation::Add if res.inserts_element(patch) => {}
+                PatchOperation::Add => {
+                    if current.is_some() {
+                        return Err(DiffPatchError::IncorrectPropertyValue(patch.subject.clone()));
+                    }
+                }
+                PatchOperation::Remove | PatchOperation::Replace => match current {
+                    None => {
+                        return Err(DiffPatchError::NonExistentProperty(patch.subject.clone()))
+                    }
+                    Some(ref value) if *value != patch.previous => {
+                        return Err(DiffPatchError::IncorrectPropertyValue(patch.subject.clone()))
+                    }
+                    Some(_) => {}
+                },
+            }
+
+            if res.apply_patch(patch).is_err() {
+                return Err(DiffPatchError::NonExistentProperty(patch.subject.clone()));
+            }
+        }
+        Ok(res)
+    }
+
+    /// The value currently at the subject of `patch`, if any, where `-` is the last element
+    fn current_value(&self, patch: &Patch) -> Option<JsonApiValue> {
+        let (name, path) = split_subject(&patch.subject);
+        match patch.patch_type {
+            PatchType::Attribute => {
+                let value = self.attributes.get(&unescape_pointer_segment(name))?;
+                value.pointer(&last_element_path(value, path)).cloned()
+            }
+            PatchType::Relationship => {
+                let data = self.get_relationship(name)?.data.as_ref()?;
+                match (data, path.get(1..)) {
+                    (_, None) => Some(to_value(data)),
+                    (IdentifierData::Multiple(identifiers), Some("-")) => {
+                        identifiers.last().map(to_value)
+                    }
+                    (IdentifierData::Multiple(identifiers), Some(index)) => {
+                        index.parse::<usize>().ok()
+                            .and_then(|index| identifiers.get(index))
+                            .map(to_value)
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
 
-            let matching = self_keys.iter().zip(other_keys.iter()).filter(|&(a, b)| a == b).count();
+    /// Whether `patch` adds an element to an array or to-many relationship at an index
+    fn inserts_element(&self, patch: &Patch) -> bool {
+        let (name, path) = split_subject(&patch.subject);
+        match patch.patch_type {
+            PatchType::Attribute => {
+                let parent = match path.rfind('/') {
+                    Some(index) => &path[..index],
+                    None => return false,
+                };
+                self.attributes.get(&unescape_pointer_segment(name))
+                    .and_then(|value| value.pointer(parent))
+                    .is_some_and(|parent| parent.is_array())
+            }
+            PatchType::Relationship => !path.is_empty(),
+        }
+    }
 
-            if matching != self_keys.len() {
-                Err(DiffPatchError::DifferentAttributeKeys)
+    fn patch_attribute(&mut self, patch: &Patch) -> Result<()> {
+        let (name, path) = split_subject(&patch.subject);
+        let name = unescape_pointer_segment(name);
+
+        if path.is_empty() {
+            if patch.operation == PatchOperation::Remove {
+                self.attributes.remove(&name);
             } else {
-                let mut patchset = PatchSet::new_for(self);
-
-                for (attr, self_value) in &self.attributes {
-                    match other.attributes.get(attr) {
-                        None => {
-                            error!("Resource::diff unable to find attribute {:?} in {:?}",
-                                   attr,
-                                   other);
+                self.attributes.insert(name, patch.next.clone());
+            }
+            return Ok(());
+        }
+
+        let value = match self.attributes.get_mut(&name) {
+            Some(value) => value,
+            None => return Err(patch_error(patch, "no such attribute")),
+        };
+        let applied = match patch.operation {
+            PatchOperation::Add => pointer_add(value, path, patch.next.clone()),
+            PatchOperation::Remove => {
+                let path = last_element_path(value, path);
+                pointer_remove(value, &path).map(|_| ())
+            }
+            PatchOperation::Replace => {
+                pointer_get_mut(value, path).map(|target| *target = patch.next.clone())
+            }
+        };
+        applied.map_err(|error| match error {
+            JsonPatchError::InvalidPointer(_) => invalid_patch(patch, "invalid subject"),
+            _ => patch_error(patch, "no such attribute value"),
+        })
+    }
+
+    fn patch_relationship(&mut self, patch: &Patch) -> Result<()> {
+        let mut segments = patch.subject.splitn(2, '/');
+        let name = segments.next().unwrap_or("").to_string();
+
+        match (segments.next(), patch.operation) {
+            (None, PatchOperation::Remove) => {
+                if let Some(ref mut relationships) = self.relationships {
+                    relationships.remove(&name);
+                }
+                if self.relationships.as_ref().is_some_and(|r| r.is_empty()) {
+                    self.relationships = None;
+                }
+            }
+            (None, _) => {
+                let data = serde_json::from_value(patch.next.clone()).map_err(|e| {
+                    invalid_patch(patch, &format!("invalid relationship data: {}", e))
+                })?;
+                self.relationships
+                    .get_or_insert_with(Relationships::new)
+                    .entry(name)
+                    .or_default()
+                    .data = Some(data);
+            }
+            (Some(index), operation) => {
+                let identifiers = match self.relationships.as_mut().and_then(|r| r.get_mut(&name)) {
+                    Some(&mut Relationship {
+                        data: Some(IdentifierData::Multiple(ref mut identifiers)), ..
+                    }) => identifiers,
+                    None => return Err(patch_error(patch, "no such relationship")),
+                    _ => return Err(invalid_patch(patch, "not a to-many relationship")),
+                };
+                let position = match index {
+                    "-" if operation == PatchOperation::Remove => identifiers.len().checked_sub(1),
+                    "-" => Some(identifiers.len()),
+                    _ => Some(index.parse::<usize>()
+                        .map_err(|_| invalid_patch(patch, "invalid index"))?),
+                };
+                if operation == PatchOperation::Remove {
+                    match position {
+                        Some(index) if index < identifiers.len() => {
+                            identifiers.remove(index);
                         }
-                        Some(other_value) => {
-                            if self_value.to_string() != other_value.to_string() {
-                                patchset.push(Patch {
-                                    patch_type: PatchType::Attribute,
-                                    subject: attr.clone(),
-                                    previous: self_value.clone(),
-                                    next: other_value.clone(),
-                                });
-                            }
+                        _ => return Err(patch_error(patch, "no such identifier")),
+                    }
+                } else {
+                    let identifier = serde_json::from_value(patch.next.clone())
+                        .map_err(|e| invalid_patch(patch, &format!("invalid identifier: {}", e)))?;
+                    match position {
+                        Some(index) if index <= identifiers.len() => {
+                            identifiers.insert(index, identifier)
                         }
+                        _ => return Err(patch_error(patch, "no such identifier")),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Patch {
+    /// The patch undoing this one, swapping `previous` and `next`
+    ///
+    /// An append to `<name>/-` is undone by a `Remove` of `<name>/-`, the last element.
+    pub fn invert(&self) -> Patch {
+        let operation = match self.operation {
+            PatchOperation::Add => PatchOperation::Remove,
+            PatchOperation::Remove => PatchOperation::Add,
+            PatchOperation::Replace => PatchOperation::Replace,
+        };
+        Patch {
+            patch_type: self.patch_type,
+            operation,
+            subject: self.subject.clone(),
+            previous: self.next.clone(),
+            next: self.previous.clone(),
+        }
+    }
+
+    /// A patch of an attribute, or of a value nested in one
+    pub fn attribute<S: Into<String>>(operation: PatchOperation, subject: S,
+                                      previous: JsonApiValue, next: JsonApiValue) -> Self {
+        Patch {
+            patch_type: PatchType::Attribute,
+            operation,
+            subject: subject.into(),
+            previous,
+            next,
+        }
+    }
+
+    /// A patch of a relationship, or of an identifier in a to-many relationship
+    pub fn relationship<S: Into<String>>(operation: PatchOperation, subject: S,
+                                         previous: JsonApiValue, next: JsonApiValue) -> Self {
+        Patch {
+            patch_type: PatchType::Relationship,
+            operation,
+            subject: subject.into(),
+            previous,
+            next,
+        }
+    }
+}
+
+/// An error for a patch the resource does not have the value for
+fn patch_error(patch: &Patch, message: &str) -> Error {
+    Error::Patch { subject: patch.subject.clone(), message: message.into() }
+}
+
+/// An error for a malformed patch
+fn invalid_patch(patch: &Patch, message: &str) -> Error {
+    Error::InvalidPatch { subject: patch.subject.clone(), message: message.into() }
+}
+
+/// Split a patch subject into the attribute or relationship name and the pointer after it
+fn split_subject(subject: &str) -> (&str, &str) {
+    match subject.find('/') {
+        Some(index) => (&subject[..index], &subject[index..]),
+        None => (subject, ""),
+    }
+}
+
+/// `path` with a last `-` token replaced by the index of the last element of its array
+fn last_element_path(value: &JsonApiValue, path: &str) -> String {
+    if let Some(parent) = path.strip_suffix("/-") {
+        if let Some(len) = value.pointer(parent)
+            .and_then(|parent| parent.as_array())
+            .map(|array| array.len())
+        {
+            if len > 0 {
+                return format!("{}/{}", parent, len - 1);
+            }
+        }
+    }
+    path.to_string()
+}
+
+/// Attribute patches for the differences between two values at `subject`, recursing into
+/// objects and arrays
+fn diff_values(subject: String, previous: &JsonApiValue, next: &JsonApiValue,
+               patchset: &mut PatchSet) {
+    match (previous, next) {
+        (JsonApiValue::Object(previous), JsonApiValue::Object(next)) => {
+            let mut keys: Vec<&String> = previous.keys().chain(next.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = format!("{}/{}", subject, pointer_segment(key));
+                match (previous.get(key), next.get(key)) {
+                    (Some(previous), Some(next)) => diff_values(path, previous, next, patchset),
+                    (Some(previous), None) => {
+                        patchset.push(Patch::attribute(PatchOperation::Remove, path,
+                                                       previous.clone(), JsonApiValue::Null))
                     }
+                    (None, Some(next)) => {
+                        patchset.push(Patch::attribute(PatchOperation::Add, path,
+                                                       JsonApiValue::Null, next.clone()))
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        (JsonApiValue::Array(previous), JsonApiValue::Array(next)) => {
+            for (index, (a, b)) in previous.iter().zip(next.iter()).enumerate() {
+                diff_values(format!("{}/{}", subject, index), a, b, patchset);
+            }
+            for index in (next.len()..previous.len()).rev() {
+                patchset.push(Patch::attribute(PatchOperation::Remove,
+                                               format!("{}/{}", subject, index),
+                                               previous[index].clone(), JsonApiValue::Null));
+            }
+            for value in next.iter().skip(previous.len()) {
+                patchset.push(Patch::attribute(PatchOperation::Add, format!("{}/-", subject),
+                                               JsonApiValue::Null, value.clone()));
+            }
+        }
+        _ if previous != next => {
+            patchset.push(Patch::attribute(PatchOperation::Replace, subject, previous.clone(),
+                                           next.clone()))
+        }
+        _ => {}
+    }
+}
 
+fn to_value<T: Serialize>(value: &T) -> JsonApiValue {
+    serde_json::to_value(value).unwrap_or(JsonApiValue::Null)
+}
+
+/// Identifiers are compared by `type`, `id` and `lid`, ignoring `meta`
+fn same_identifier(a: &ResourceIdentifier, b: &ResourceIdentifier) -> bool {
+    a._type == b._type && a.id == b.id && a.lid == b.lid
+}
+
+fn diff_identifier_data(name: &str, previous: &IdentifierData, next: &IdentifierData,
+                        patchset: &mut PatchSet) {
+    match (previous, next) {
+        (IdentifierData::Multiple(previous_identifiers),
+         IdentifierData::Multiple(next_identifiers)) => {
+            let removed: Vec<usize> = (0..previous_identifiers.len())
+                .rev()
+                .filter(|&index| {
+                    !next_identifiers.iter()
+                        .any(|other| same_identifier(&previous_identifiers[index], other))
+                })
+                .collect();
+            let added: Vec<&ResourceIdentifier> = next_identifiers.iter()
+                .filter(|identifier| {
+                    !previous_identifiers.iter().any(|other| same_identifier(identifier, other))
+                })
+                .collect();
+
+            // Removals and appends can not reorder the identifiers, which then replace the data
+            let kept = previous_identifiers.iter()
+                .enumerate()
+                .filter(|&(index, _)| !removed.contains(&index))
+                .map(|(_, identifier)| identifier);
+            let in_order = kept.clone().count() + added.len() == next_identifiers.len() &&
+                kept.chain(added.iter().cloned())
+                    .zip(next_identifiers)
+                    .all(|(a, b)| same_identifier(a, b));
+            if !in_order {
+                patchset.push(Patch::relationship(PatchOperation::Replace, name.to_string(),
+                                                  to_value(previous), to_value(next)));
+                return;
+            }
+
+            for index in removed {
+                patchset.push(Patch::relationship(PatchOperation::Remove,
+                                                  format!("{}/{}", name, index),
+                                                  to_value(&previous_identifiers[index]),
+                                                  JsonApiValue::Null));
+            }
+            for identifier in added {
+                patchset.push(Patch::relationship(PatchOperation::Add, format!("{}/-", name),
+                                                  JsonApiValue::Null, to_value(identifier)));
+            }
+        }
+        (IdentifierData::Single(a), IdentifierData::Single(b))
+            if same_identifier(a, b) => {}
+        (IdentifierData::None, IdentifierData::None) => {}
+        _ => {
+            patchset.push(Patch::relationship(PatchOperation::Replace, name.to_string(),
+                                              to_value(previous), to_value(next)))
+        }
+    }
+}
+
+impl ResourceIdentifier {
+    /// Returns `true` if this identifier refers to `resource`, by `id` or by `lid`
+    pub fn identifies(&self, resource: &Resource) -> bool {
+        if self._type != resource._type {
+            return false;
+        }
+        match (self.id.as_ref(), self.lid.as_ref()) {
+            (Some(id), _) if resource.id.as_ref() == Some(id) => true,
+            (_, Some(lid)) => resource.lid.as_ref() == Some(lid),
+            _ => false,
+        }
+    }
+}
+
+impl Resource {
+    /// Collect the specification violations in this resource, located relative to `pointer`
+    fn validate_at(&self, pointer: &str, errors: &mut Vec<DocumentValidationError>) {
+        if !is_valid_member_name(&self._type) {
+            errors.push(DocumentValidationError::InvalidMemberName(format!("{}/type", pointer)));
+        }
+
+        let mut attribute_names: Vec<&String> = self.attributes.keys().collect();
+        attribute_names.sort();
+        for name in attribute_names {
+            let attribute_pointer = format!("{}/attributes/{}", pointer, pointer_segment(name));
+            if RESERVED_ATTRIBUTE_NAMES.contains(&name.as_str()) {
+                errors.push(DocumentValidationError::ReservedFieldName(attribute_pointer));
+            } else if !is_valid_member_name(name) {
+                errors.push(DocumentValidationError::InvalidMemberName(attribute_pointer));
+            } else {
+                validate_member_names(&self.attributes[name], &attribute_pointer, errors);
+            }
+        }
+
+        if let Some(ref relationships) = self.relationships {
+            let mut names: Vec<&String> = relationships.keys().collect();
+            names.sort();
+            for name in names {
+                let relationship_pointer =
+                    format!("{}/relationships/{}", pointer, pointer_segment(name));
+                if RESERVED_RELATIONSHIP_NAMES.contains(&name.as_str()) {
+                    errors.push(DocumentValidationError::ReservedFieldName(
+                        relationship_pointer.clone()));
+                } else if !is_valid_member_name(name) {
+                    errors.push(DocumentValidationError::InvalidMemberName(
+                        relationship_pointer.clone()));
+                }
+                if self.attributes.contains_key(name) {
+                    errors.push(DocumentValidationError::FieldNameCollision(
+                        relationship_pointer.clone()));
                 }
+                if !relationships[name].has_content() {
+                    errors.push(DocumentValidationError::EmptyRelationship(relationship_pointer));
+                }
+            }
+        }
+    }
+}
 
-                Ok(patchset)
+impl JsonApiInfo {
+    /// Collect the specification violations in this object, located relative to `pointer`
+    fn validate_at(&self, pointer: &str, errors: &mut Vec<DocumentValidationError>) {
+        if let Some(ref version) = self.version {
+            let mut parts = version.split('.');
+            let valid = match (parts.next(), parts.next(), parts.next()) {
+                (Some(major), Some(minor), None) => {
+                    !major.is_empty() && !minor.is_empty() &&
+                    major.chars().chain(minor.chars()).all(|c| c.is_ascii_digit())
+                }
+                _ => false,
+            };
+            if !valid {
+                errors.push(DocumentValidationError::InvalidJsonApiObject(
+                    format!("{}/version", pointer)));
+            }
+        }
+
+        for &(member, uris) in &[("ext", &self.ext), ("profile", &self.profile)] {
+            if let Some(ref uris) = *uris {
+                for (index, uri) in uris.iter().enumerate() {
+                    if !is_absolute_uri(uri) {
+                        errors.push(DocumentValidationError::InvalidJsonApiObject(
+                            format!("{}/{}/{}", pointer, member, index)));
+                    }
+                }
             }
         }
     }
+}
 
-    pub fn patch(&mut self, patchset: PatchSet) -> Result<Resource> {
-        let mut res = self.clone();
-        for patch in &patchset.patches {
-            res.attributes.insert(patch.subject.clone(), patch.next.clone());
+/// Attribute names that would clash with the members of a resource object
+const RESERVED_ATTRIBUTE_NAMES: &[&str] = &["relationships", "links", "id", "type"];
+/// Relationship names that would clash with the members of a resource object
+const RESERVED_RELATIONSHIP_NAMES: &[&str] = &["id", "type"];
+/// Member names reserved in the objects of complex attribute values
+const RESERVED_NESTED_NAMES: &[&str] = &["relationships", "links"];
+
+/// Check a member name against the naming rules of the specification.
+///
+/// Allows ASCII letters and digits, non-ASCII characters, and `-`, `_` or ` ` other than at
+/// the start or end. A leading `@` is allowed for `@-Members`.
+///
+/// ```
+/// use jsonapi::api::is_valid_member_name;
+///
+/// assert!(is_valid_member_name("first-name"));
+/// assert!(!is_valid_member_name("atomic:operations"));
+/// assert!(!is_valid_member_name("_private"));
+/// assert!(!is_valid_member_name("address.city"));
+/// ```
+pub fn is_valid_member_name(name: &str) -> bool {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    let count = name.chars().count();
+    count > 0 &&
+    name.chars().enumerate().all(|(index, c)| {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => true,
+            '-' | '_' | ' ' => index != 0 && index != count - 1,
+            c => c >= '\u{80}',
         }
-        Ok(res)
+    })
+}
+
+/// Check an extension member name, a namespace and a member name joined by `:`
+///
+/// ```
+/// use jsonapi::api::is_valid_extension_member_name;
+///
+/// assert!(is_valid_extension_member_name("atomic:operations"));
+/// assert!(!is_valid_extension_member_name("operations"));
+/// assert!(!is_valid_extension_member_name("a:b:c"));
+/// ```
+pub fn is_valid_extension_member_name(name: &str) -> bool {
+    let mut parts = name.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(namespace), Some(member)) => {
+            !namespace.starts_with('@') && is_valid_member_name(namespace) &&
+            is_valid_member_name(member)
+        }
+        _ => false,
     }
 }
 
+/// Check the member names of the objects in a (complex) attribute value
+fn validate_member_names(value: &JsonApiValue,
+                         pointer: &str,
+                         errors: &mut Vec<DocumentValidationError>) {
+    match *value {
+        JsonApiValue::Object(ref object) => {
+            for (name, value) in object {
+                let member_pointer = format!("{}/{}", pointer, pointer_segment(name));
+                if RESERVED_NESTED_NAMES.contains(&name.as_str()) {
+                    errors.push(DocumentValidationError::ReservedFieldName(member_pointer));
+                } else if is_valid_member_name(name) {
+                    validate_member_names(value, &member_pointer, errors);
+                } else {
+                    errors.push(DocumentValidationError::InvalidMemberName(member_pointer));
+                }
+            }
+        }
+        JsonApiValue::Array(ref array) => {
+            for (index, value) in array.iter().enumerate() {
+                validate_member_names(value, &format!("{}/{}", pointer, index), errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_absolute_uri(uri: &str) -> bool {
+    match uri.find(':') {
+        Some(index) if index > 0 => {
+            let scheme = &uri[..index];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic()) &&
+            scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        }
+        _ => false,
+    }
+}
+
+/// Escape a member name for use as a JSON pointer reference token
+pub(crate) fn pointer_segment(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+/// Unescape a JSON pointer reference token, the reverse of `pointer_segment`
+pub(crate) fn unescape_pointer_segment(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
 impl FromStr for Resource {
     type Err = Error;
 
@@ -411,59 +1524,223 @@ impl FromStr for Resource {
     /// assert_eq!(data.is_ok(), true);
     /// ```
     fn from_str(s: &str) -> Result<Self> {
-        serde_json::from_str(s).chain_err(|| "Error parsing resource" )
+        Ok(serde_json::from_str(s)?)
     }
 }
 
 
 impl Relationship {
+    /// Returns `false` if none of `links`, `data` or `meta` is present
+    pub fn has_content(&self) -> bool {
+        self.data.is_some() || self.links.is_some() || self.meta.is_some()
+    }
+
+    /// The id of a to-one relationship, failing with `MissingId` if the identifier has only a
+    /// `lid`
     pub fn as_id(&self) -> std::result::Result<Option<&JsonApiId>, RelationshipAssumptionError> {
         match self.data {
-            IdentifierData::None => Ok(None),
-            IdentifierData::Multiple(_) => Err(RelationshipAssumptionError::RelationshipIsAList),
-            IdentifierData::Single(ref data) => Ok(Some(&data.id)),
+            None | Some(IdentifierData::None) => Ok(None),
+            Some(IdentifierData::Multiple(_)) => {
+                Err(RelationshipAssumptionError::RelationshipIsAList)
+            }
+            Some(IdentifierData::Single(ref data)) => match data.id {
+                Some(ref id) => Ok(Some(id)),
+                None => Err(RelationshipAssumptionError::MissingId),
+            },
         }
     }
 
-    pub fn as_ids(&self) -> std::result::Result<Option<JsonApiIds>, RelationshipAssumptionError> {
+    /// The ids of a to-many relationship, failing with `MissingId` if an identifier has only a
+    /// `lid`
+    pub fn as_ids(&self) -> std::result::Result<Option<JsonApiIds<'_>>, RelationshipAssumptionError> {
         match self.data {
-            IdentifierData::None => Ok(None),
-            IdentifierData::Single(_) => Err(RelationshipAssumptionError::RelationshipIsNotAList),
-            IdentifierData::Multiple(ref data) => Ok(Some(data.iter().map(|x| &x.id).collect())),
+            None | Some(IdentifierData::None) => Ok(None),
+            Some(IdentifierData::Single(_)) => {
+                Err(RelationshipAssumptionError::RelationshipIsNotAList)
+            }
+            Some(IdentifierData::Multiple(ref data)) => {
+                data.iter()
+                    .map(|x| x.id.as_ref().ok_or(RelationshipAssumptionError::MissingId))
+                    .collect::<std::result::Result<_, _>>()
+                    .map(Some)
+            }
         }
     }
 }
 
 /// Top-level (Document) JSON-API specification violations
-#[derive(Debug, Clone, PartialEq, Copy)]
+///
+/// Each violation is located in the document by a JSON pointer, see `pointer`.
+#[derive(Debug, Clone, PartialEq)]
 pub enum DocumentValidationError {
     IncludedWithoutData,
     DataWithErrors,
     MissingContent,
+    /// A relationship object without any of `links`, `data` or `meta`
+    EmptyRelationship(String),
+    /// A member name that does not meet the naming rules, see `is_valid_member_name`
+    InvalidMemberName(String),
+    /// An attribute or relationship with a name reserved for resource object members
+    ReservedFieldName(String),
+    /// A relationship with the same name as an attribute of the same resource
+    FieldNameCollision(String),
+    /// A resource with the same `type` and `id` as an earlier one in `data` or `included`
+    DuplicateResource(String),
+    /// A resource with the same `type` and `lid` as an earlier one in `data` or `included`
+    DuplicateLocalId(String),
+    /// A resource without an `id` in a response document
+    MissingResourceId(String),
+    /// An invalid member of the `jsonapi` object
+    InvalidJsonApiObject(String),
 }
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+impl DocumentValidationError {
+    /// The JSON pointer to the offending member, `""` being the whole document
+    pub fn pointer(&self) -> &str {
+        match *self {
+            DocumentValidationError::IncludedWithoutData => "/included",
+            DocumentValidationError::DataWithErrors => "/errors",
+            DocumentValidationError::MissingContent => "",
+            DocumentValidationError::EmptyRelationship(ref pointer) |
+            DocumentValidationError::InvalidMemberName(ref pointer) |
+            DocumentValidationError::ReservedFieldName(ref pointer) |
+            DocumentValidationError::FieldNameCollision(ref pointer) |
+            DocumentValidationError::DuplicateResource(ref pointer) |
+            DocumentValidationError::DuplicateLocalId(ref pointer) |
+            DocumentValidationError::MissingResourceId(ref pointer) |
+            DocumentValidationError::InvalidJsonApiObject(ref pointer) => pointer,
+        }
+    }
+
+    /// A `400 Bad Request` `JsonApiError` for this violation, with its `source.pointer`
+    pub fn to_jsonapi_error(&self) -> JsonApiError {
+        JsonApiError {
+            status: Some("400".into()),
+            title: Some("Invalid document".into()),
+            detail: Some(self.to_string()),
+            source: Some(ErrorSource {
+                pointer: Some(self.pointer().into()),
+                parameter: None,
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for DocumentValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            DocumentValidationError::IncludedWithoutData => "included is present without data",
+            DocumentValidationError::DataWithErrors => "data and errors must not coexist",
+            DocumentValidationError::MissingContent => {
+                "a document must contain at least one of data, errors or meta"
+            }
+            DocumentValidationError::EmptyRelationship(_) => {
+                "a relationship must contain at least one of links, data or meta"
+            }
+            DocumentValidationError::InvalidMemberName(_) => "invalid member name",
+            DocumentValidationError::ReservedFieldName(_) => "reserved field name",
+            DocumentValidationError::FieldNameCollision(_) => {
+                "an attribute and a relationship must not share a name"
+            }
+            DocumentValidationError::DuplicateResource(_) => {
+                "a resource with the same type and id is already present"
+            }
+            DocumentValidationError::DuplicateLocalId(_) => {
+                "a resource with the same type and lid is already present"
+            }
+            DocumentValidationError::MissingResourceId(_) => "resource without an id",
+            DocumentValidationError::InvalidJsonApiObject(_) => "invalid jsonapi object member",
+        };
+        write!(f, "{} at '{}'", description, self.pointer())
+    }
+}
+
+/// Errors accessing resource attributes, carrying the attribute name or path
+#[derive(Debug, Clone, PartialEq)]
 pub enum JsonApiDataError {
-    AttributeNotFound,
-    IncompatibleAttributeType,
+    AttributeNotFound(String),
+    IncompatibleAttributeType(String),
+}
+
+impl fmt::Display for JsonApiDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JsonApiDataError::AttributeNotFound(ref name) => {
+                write!(f, "attribute '{}' not found", name)
+            }
+            JsonApiDataError::IncompatibleAttributeType(ref name) => {
+                write!(f, "attribute '{}' has an incompatible type", name)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub enum RelationshipAssumptionError {
     RelationshipIsAList,
     RelationshipIsNotAList,
+    /// A resource identifier has a `lid` but no `id`
+    MissingId,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DiffPatchError {
     IncompatibleTypes(String, String),
-    DifferentAttributeKeys,
+    /// The resource and the `PatchSet` have different ids, given in that order
+    IncompatibleIds(String, String),
     NonExistentProperty(String),
     IncorrectPropertyValue(String),
+    /// The resource to diff or patch has no id
+    MissingId,
+    /// A change to this relationship that patches can not express, such as removing its `data`
+    /// while keeping its `links`
+    UnsupportedChange(String),
 }
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+/// Errors converting or applying an RFC 6902 JSON Patch, carrying the offending path
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonPatchError {
+    /// The path is not a valid JSON pointer
+    InvalidPointer(String),
+    /// The path, or the parent of a path to add to, does not exist
+    PathNotFound(String),
+    /// A `test` operation did not match
+    TestFailed(String),
+    /// The patched value is no longer a valid resource
+    InvalidResource(String),
+    /// The operation can not be expressed as a `Patch`
+    Unsupported(String),
+}
+
+impl fmt::Display for JsonPatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JsonPatchError::InvalidPointer(ref path) => write!(f, "invalid pointer '{}'", path),
+            JsonPatchError::PathNotFound(ref path) => write!(f, "path '{}' not found", path),
+            JsonPatchError::TestFailed(ref path) => write!(f, "test failed at '{}'", path),
+            JsonPatchError::InvalidResource(ref reason) => {
+                write!(f, "patched value is not a resource: {}", reason)
+            }
+            JsonPatchError::Unsupported(ref path) => {
+                write!(f, "unsupported operation on '{}'", path)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Copy)]
+#[serde(rename_all = "lowercase")]
 pub enum PatchType {
     Relationship,
     Attribute,
 }
+
+/// How a `Patch` changes its subject
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add,
+    Remove,
+    Replace,
+}
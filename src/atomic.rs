@@ -0,0 +1,503 @@
+//! The Atomic Operations extension, `https://jsonapi.org/ext/atomic`
+//!
+//! `execute` runs the operations of an `AtomicDocument` against an `AtomicStore`, all or
+//! nothing, resolving local ids (`lid`) along the way.
+
+// Operations fail with a `JsonApiError`, which is returned as is in the error document
+#![allow(clippy::result_large_err)]
+
+use api::*;
+use errors::Error;
+use serde::{Deserialize, Deserializer};
+use serde_json;
+use std::collections::HashMap;
+
+/// URI identifying the Atomic Operations extension
+pub const EXTENSION_URI: &str = "https://jsonapi.org/ext/atomic";
+
+/// Request or response document of the Atomic Operations extension
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AtomicDocument {
+    #[serde(rename = "atomic:operations")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operations: Option<Vec<Operation>>,
+    #[serde(rename = "atomic:results")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<Vec<OperationResult>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<Links>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jsonapi: Option<JsonApiInfo>,
+}
+
+/// The `op` code of an operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Copy)]
+pub enum OperationCode {
+    #[serde(rename = "add")]
+    Add,
+    #[serde(rename = "update")]
+    Update,
+    #[serde(rename = "remove")]
+    Remove,
+}
+
+/// A single operation
+///
+/// The target is given by either `ref` or `href`. When neither is given, the target is the
+/// resource in `data`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub op: OperationCode,
+    #[serde(rename = "ref")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _ref: Option<OperationRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<OperationData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+}
+
+/// Operation target, a resource or one of its relationships
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct OperationRef {
+    #[serde(rename = "type")]
+    pub _type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<JsonApiId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relationship: Option<String>,
+}
+
+/// Operation `data`, a resource for resource operations and resource linkage for relationship
+/// operations
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum OperationData {
+    Resource(Box<Resource>),
+    Identifiers(IdentifierData),
+}
+
+/// The result of a successful operation, in `atomic:results`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct OperationResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Resource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+}
+
+/// Storage the operations are applied to
+///
+/// A `source.pointer` in an error is relative to the operation, such as `/data/attributes/title`.
+pub trait AtomicStore {
+    /// Create a resource, returning it as stored, with its `id`
+    fn add(&mut self, resource: Resource) -> Result<Resource, JsonApiError>;
+
+    /// Update a resource, returning it if the store changed it beyond the request
+    fn update(&mut self, resource: Resource)
+        -> Result<Option<Resource>, JsonApiError>;
+
+    fn remove(&mut self, target: &ResourceIdentifier) -> Result<(), JsonApiError>;
+
+    /// Replace the linkage of a relationship
+    fn update_relationship(&mut self,
+                           target: &ResourceIdentifier,
+                           relationship: &str,
+                           data: IdentifierData)
+                           -> Result<(), JsonApiError>;
+
+    /// Add members to a to-many relationship
+    fn add_to_relationship(&mut self,
+                           target: &ResourceIdentifier,
+                           relationship: &str,
+                           data: ResourceIdentifiers)
+                           -> Result<(), JsonApiError>;
+
+    /// Remove members from a to-many relationship
+    fn remove_from_relationship(&mut self,
+                                target: &ResourceIdentifier,
+                                relationship: &str,
+                                data: ResourceIdentifiers)
+                                -> Result<(), JsonApiError>;
+
+    /// Called before the first operation
+    fn begin(&mut self) -> Result<(), JsonApiError> {
+        Ok(())
+    }
+
+    /// Called after all operations succeeded
+    fn commit(&mut self) -> Result<(), JsonApiError> {
+        Ok(())
+    }
+
+    /// Called when an operation failed, to undo the earlier operations
+    fn rollback(&mut self) {}
+}
+
+/// Operation as read, before `data` is interpreted according to the target
+#[derive(Deserialize)]
+struct RawOperation {
+    op: OperationCode,
+    #[serde(rename = "ref")]
+    _ref: Option<OperationRef>,
+    href: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    data: Option<JsonApiValue>,
+    meta: Option<Meta>,
+}
+
+impl<'de> Deserialize<'de> for Operation {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        use serde::de::Error;
+
+        let raw = RawOperation::deserialize(deserializer)?;
+        let mut operation = Operation {
+            op: raw.op,
+            _ref: raw._ref,
+            href: raw.href,
+            data: None,
+            meta: raw.meta,
+        };
+        if let Some(data) = raw.data {
+            operation.data = Some(if operation.targets_relationship() {
+                OperationData::Identifiers(serde_json::from_value(data).map_err(D::Error::custom)?)
+            } else {
+                OperationData::Resource(serde_json::from_value(data).map_err(D::Error::custom)?)
+            });
+        }
+        Ok(operation)
+    }
+}
+
+impl Operation {
+    /// Returns `true` if the operation targets a relationship rather than a resource
+    pub fn targets_relationship(&self) -> bool {
+        match (self._ref.as_ref(), self.href.as_ref()) {
+            (Some(target), _) => target.relationship.is_some(),
+            (None, Some(href)) => {
+                parse_href(self.op, href).is_some_and(|target| target.relationship.is_some())
+            }
+            (None, None) => false,
+        }
+    }
+}
+
+impl From<OperationRef> for ResourceIdentifier {
+    fn from(target: OperationRef) -> Self {
+        ResourceIdentifier {
+            _type: target._type,
+            id: target.id,
+            lid: target.lid,
+            meta: None,
+        }
+    }
+}
+
+/// A resolved operation target
+struct Target {
+    identifier: ResourceIdentifier,
+    relationship: Option<String>,
+}
+
+/// Ids assigned to resources created in the same request, by type and `lid`
+struct LocalIds(HashMap<(String, String), JsonApiId>);
+
+impl LocalIds {
+    fn resolve(&self, identifier: &mut ResourceIdentifier) -> Result<(), String> {
+        if identifier.id.is_some() {
+            return Ok(());
+        }
+        match identifier.lid {
+            None => Err(format!("No id or lid given for a resource of type '{}'",
+                                identifier._type)),
+            Some(ref lid) => {
+                match self.0.get(&(identifier._type.clone(), lid.clone())) {
+                    None => Err(format!("Unknown lid '{}' for type '{}'", lid, identifier._type)),
+                    Some(id) => {
+                        identifier.id = Some(id.clone());
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    fn resolve_all(&self, data: &mut IdentifierData) -> Result<(), String> {
+        match *data {
+            IdentifierData::None => Ok(()),
+            IdentifierData::Single(ref mut identifier) => self.resolve(identifier),
+            IdentifierData::Multiple(ref mut identifiers) => {
+                for identifier in identifiers.iter_mut() {
+                    self.resolve(identifier)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn resolve_relationships(&self, resource: &mut Resource)
+        -> Result<(), String>
+    {
+        if let Some(ref mut relationships) = resource.relationships {
+            for relationship in relationships.values_mut() {
+                if let Some(ref mut data) = relationship.data {
+                    self.resolve_all(data)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run the operations of `document` in order against `store`.
+///
+/// On failure the store is rolled back and an error document locating the failing operation is
+/// returned.
+pub fn execute<S: AtomicStore>(store: &mut S, document: &AtomicDocument)
+    -> Result<AtomicDocument, JsonApiDocument>
+{
+    let operations = match document.operations {
+        Some(ref operations) => operations,
+        None => {
+            return Err(error_document(bad_request("Missing atomic:operations",
+                                                  Some("/atomic:operations".into()))))
+        }
+    };
+
+    store.begin().map_err(error_document)?;
+
+    let mut local_ids = LocalIds(HashMap::new());
+    let mut results = Vec::new();
+
+    for (index, operation) in operations.iter().enumerate() {
+        match execute_operation(store, operation, &mut local_ids) {
+            Ok(result) => results.push(result),
+            Err(mut error) => {
+                store.rollback();
+                let pointer = format!("/atomic:operations/{}", index);
+                let mut source = error.source.take().unwrap_or_default();
+                source.pointer = Some(match source.pointer {
+                    Some(ref relative) => format!("{}{}", pointer, relative),
+                    None => pointer,
+                });
+                error.source = Some(source);
+                return Err(error_document(error));
+            }
+        }
+    }
+
+    if let Err(error) = store.commit() {
+        store.rollback();
+        return Err(error_document(error));
+    }
+
+    Ok(AtomicDocument {
+        results: Some(results),
+        jsonapi: Some(JsonApiInfo {
+            version: Some("1.1".into()),
+            ext: Some(vec![EXTENSION_URI.into()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+fn execute_operation<S: AtomicStore>(store: &mut S,
+                                     operation: &Operation,
+                                     local_ids: &mut LocalIds)
+                                     -> Result<OperationResult, JsonApiError> {
+    let target = resolve_target(operation)?;
+
+    match (operation.op, target.relationship) {
+        (OperationCode::Add, None) => {
+            let mut resource = expect_resource(operation)?;
+            local_ids.resolve_relationships(&mut resource)
+                .map_err(|detail| bad_request(&detail, Some("/data/relationships".into())))?;
+            let lid = resource.lid.clone();
+            let created = store.add(resource)?;
+            if let (Some(lid), Some(id)) = (lid, created.id.clone()) {
+                local_ids.0.insert((created._type.clone(), lid), id);
+            }
+            Ok(OperationResult {
+                data: Some(created),
+                meta: None,
+            })
+        }
+        (OperationCode::Update, None) => {
+            let mut resource = expect_resource(operation)?;
+            check_target(&target.identifier, &resource)?;
+            let mut identifier = target.identifier;
+            identifier.lid = identifier.lid.or_else(|| resource.lid.clone());
+            identifier.id = identifier.id.or_else(|| resource.id.clone());
+            local_ids.resolve(&mut identifier).map_err(|detail| bad_request(&detail, None))?;
+            resource.id = identifier.id;
+            local_ids.resolve_relationships(&mut resource)
+                .map_err(|detail| bad_request(&detail, Some("/data/relationships".into())))?;
+            Ok(OperationResult {
+                data: store.update(resource)?,
+                meta: None,
+            })
+        }
+        (OperationCode::Remove, None) => {
+            let mut identifier = target.identifier;
+            local_ids.resolve(&mut identifier).map_err(|detail| bad_request(&detail, None))?;
+            store.remove(&identifier)?;
+            Ok(OperationResult::default())
+        }
+        (op, Some(relationship)) => {
+            let mut identifier = target.identifier;
+            local_ids.resolve(&mut identifier).map_err(|detail| bad_request(&detail, None))?;
+            let mut data = match operation.data {
+                Some(OperationData::Identifiers(ref data)) => data.clone(),
+                _ => {
+                    return Err(bad_request("Relationship operations require resource linkage \
+                                            in data",
+                                           Some("/data".into())))
+                }
+            };
+            local_ids.resolve_all(&mut data)
+                .map_err(|detail| bad_request(&detail, Some("/data".into())))?;
+            match (op, data) {
+                (OperationCode::Update, data) => {
+                    store.update_relationship(&identifier, &relationship, data)?
+                }
+                (OperationCode::Add, IdentifierData::Multiple(data)) => {
+                    store.add_to_relationship(&identifier, &relationship, data)?
+                }
+                (OperationCode::Remove, IdentifierData::Multiple(data)) => {
+                    store.remove_from_relationship(&identifier, &relationship, data)?
+                }
+                _ => {
+                    return Err(bad_request("Adding to or removing from a relationship requires \
+                                            an array of resource identifiers",
+                                           Some("/data".into())))
+                }
+            }
+            Ok(OperationResult::default())
+        }
+    }
+}
+
+/// Determine the target from `ref`, `href` or `data`, in that order
+fn resolve_target(operation: &Operation) -> Result<Target, JsonApiError> {
+    if let Some(ref target) = operation._ref {
+        let relationship = target.relationship.clone();
+        return Ok(Target {
+            identifier: target.clone().into(),
+            relationship,
+        });
+    }
+
+    if let Some(ref href) = operation.href {
+        return parse_href(operation.op, href)
+            .ok_or_else(|| bad_request(&format!("Unsupported href '{}'", href),
+                                       Some("/href".into())));
+    }
+
+    match operation.data {
+        Some(OperationData::Resource(ref resource)) => {
+            Ok(Target {
+                identifier: resource.as_identifier(),
+                relationship: None,
+            })
+        }
+        _ => Err(bad_request("The operation has no ref, href or resource data", None)),
+    }
+}
+
+/// Read `/{type}`, `/{type}/{id}` or `/{type}/{id}/relationships/{name}` from the end of an
+/// `href` path
+fn parse_href(op: OperationCode, href: &str) -> Option<Target> {
+    let path = href.split(&['?', '#'][..]).next().unwrap_or("");
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let count = segments.len();
+
+    let identifier = |_type: &str, id: Option<&str>| {
+        ResourceIdentifier {
+            _type: _type.into(),
+            id: id.map(|id| id.into()),
+            ..Default::default()
+        }
+    };
+
+    if count >= 4 && segments[count - 2] == "relationships" {
+        Some(Target {
+            identifier: identifier(segments[count - 4], Some(segments[count - 3])),
+            relationship: Some(segments[count - 1].into()),
+        })
+    } else if op == OperationCode::Add && count >= 1 {
+        Some(Target {
+            identifier: identifier(segments[count - 1], None),
+            relationship: None,
+        })
+    } else if count >= 2 {
+        Some(Target {
+            identifier: identifier(segments[count - 2], Some(segments[count - 1])),
+            relationship: None,
+        })
+    } else {
+        None
+    }
+}
+
+/// Fail if the `type`, `id` or `lid` of the target conflict with those of the resource in `data`
+fn check_target(target: &ResourceIdentifier, resource: &Resource) -> Result<(), JsonApiError> {
+    let conflict = |member: &str| {
+        Error::InvalidDocument {
+            pointer: format!("/data/{}", member),
+            message: format!("{} of the resource does not match the target", member),
+        }.to_jsonapi_error()
+    };
+    if target._type != resource._type {
+        return Err(conflict("type"));
+    }
+    if let (Some(target), Some(resource)) = (target.id.as_ref(), resource.id.as_ref()) {
+        if target != resource {
+            return Err(conflict("id"));
+        }
+    }
+    if let (Some(target), Some(resource)) = (target.lid.as_ref(), resource.lid.as_ref()) {
+        if target != resource {
+            return Err(conflict("lid"));
+        }
+    }
+    Ok(())
+}
+
+fn expect_resource(operation: &Operation) -> Result<Resource, JsonApiError> {
+    match operation.data {
+        Some(OperationData::Resource(ref resource)) => Ok((**resource).clone()),
+        _ => Err(bad_request("Resource operations require a resource object in data",
+                             Some("/data".into()))),
+    }
+}
+
+fn bad_request(detail: &str, pointer: Option<String>) -> JsonApiError {
+    JsonApiError {
+        status: Some("400".into()),
+        title: Some("Invalid operation".into()),
+        detail: Some(detail.into()),
+        source: pointer.map(|pointer| {
+            ErrorSource {
+                pointer: Some(pointer),
+                parameter: None,
+            }
+        }),
+        ..Default::default()
+    }
+}
+
+fn error_document(error: JsonApiError) -> JsonApiDocument {
+    JsonApiDocument {
+        errors: Some(vec![error]),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,351 @@
+//! Zero-copy variants of the `api` document types
+//!
+//! `BorrowedDocument` borrows `type`, `id` and `lid` from the input where possible, and keeps
+//! attributes, `links` and `meta` as unparsed `RawValue`s.
+//!
+//! ```
+//! use jsonapi::borrowed::BorrowedDocument;
+//!
+//! let json = r#"{
+//!   "data": { "type": "articles", "id": "1", "attributes": { "title": "Rails is Omakase" } }
+//! }"#;
+//!
+//! let doc = BorrowedDocument::parse(json).unwrap();
+//! let article = doc.primary()[0];
+//! assert_eq!(article._type, "articles");
+//! assert_eq!(article.get_attribute_raw("title").unwrap().get(), r#""Rails is Omakase""#);
+//!
+//! let owned = doc.to_document().unwrap();
+//! assert!(owned.is_valid());
+//! ```
+
+use api::*;
+use errors::*;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, MapAccess, SeqAccess,
+                Visitor};
+use serde_json;
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Attribute values, borrowed as unparsed JSON
+pub type BorrowedAttributes<'a> = HashMap<Cow<'a, str>, &'a RawValue>;
+pub type BorrowedRelationships<'a> = HashMap<Cow<'a, str>, BorrowedRelationship<'a>>;
+
+/// Borrowed variant of `ResourceIdentifier`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BorrowedIdentifier<'a> {
+    #[serde(rename = "type", borrow)]
+    pub _type: Cow<'a, str>,
+    #[serde(default, borrow, deserialize_with = "borrow_optional_str")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Cow<'a, str>>,
+    #[serde(default, borrow, deserialize_with = "borrow_optional_str")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lid: Option<Cow<'a, str>>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<&'a RawValue>,
+}
+
+/// Borrowed variant of `Resource`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BorrowedResource<'a> {
+    #[serde(rename = "type", borrow)]
+    pub _type: Cow<'a, str>,
+    #[serde(default, borrow, deserialize_with = "borrow_optional_str")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Cow<'a, str>>,
+    #[serde(default, borrow, deserialize_with = "borrow_optional_str")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lid: Option<Cow<'a, str>>,
+    #[serde(default, borrow, deserialize_with = "borrow_keys")]
+    pub attributes: BorrowedAttributes<'a>,
+    #[serde(default, borrow, deserialize_with = "borrow_optional_keys")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relationships: Option<BorrowedRelationships<'a>>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub links: Option<&'a RawValue>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<&'a RawValue>,
+}
+
+/// Borrowed variant of `Relationship`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BorrowedRelationship<'a> {
+    #[serde(default, borrow, deserialize_with = "deserialize_present")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<BorrowedIdentifierData<'a>>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub links: Option<&'a RawValue>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<&'a RawValue>,
+}
+
+/// Borrowed variant of `PrimaryData`
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum BorrowedPrimaryData<'a> {
+    None,
+    Single(Box<BorrowedResource<'a>>),
+    Multiple(Vec<BorrowedResource<'a>>),
+}
+
+/// Borrowed variant of `IdentifierData`
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum BorrowedIdentifierData<'a> {
+    None,
+    Single(BorrowedIdentifier<'a>),
+    Multiple(Vec<BorrowedIdentifier<'a>>),
+}
+
+/// Borrowed variant of `JsonApiDocument`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BorrowedDocument<'a> {
+    #[serde(default, borrow, deserialize_with = "deserialize_present")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<BorrowedPrimaryData<'a>>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub included: Option<Vec<BorrowedResource<'a>>>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub links: Option<&'a RawValue>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<&'a RawValue>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub errors: Option<&'a RawValue>,
+    #[serde(default, borrow, skip_serializing_if = "Option::is_none")]
+    pub jsonapi: Option<&'a RawValue>,
+}
+
+impl<'a> BorrowedDocument<'a> {
+    /// Deserialize a document, borrowing from `s`
+    pub fn parse(s: &'a str) -> Result<Self> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    /// The resources in `data`
+    pub fn primary(&self) -> Vec<&BorrowedResource<'a>> {
+        match self.data {
+            Some(BorrowedPrimaryData::Single(ref resource)) => vec![resource],
+            Some(BorrowedPrimaryData::Multiple(ref resources)) => resources.iter().collect(),
+            Some(BorrowedPrimaryData::None) | None => Vec::new(),
+        }
+    }
+
+    /// Convert to the owned `JsonApiDocument`, parsing the raw values
+    pub fn to_document(&self) -> Result<JsonApiDocument> {
+        let data = match self.data {
+            None => None,
+            Some(BorrowedPrimaryData::None) => Some(PrimaryData::None),
+            Some(BorrowedPrimaryData::Single(ref resource)) => {
+                Some(PrimaryData::Single(Box::new(resource.to_resource()?)))
+            }
+            Some(BorrowedPrimaryData::Multiple(ref resources)) => {
+                Some(PrimaryData::Multiple(to_resources(resources)?))
+            }
+        };
+        let included = match self.included {
+            Some(ref resources) => Some(to_resources(resources)?),
+            None => None,
+        };
+        Ok(JsonApiDocument {
+            data,
+            included,
+            links: parse_raw(self.links)?,
+            meta: parse_raw(self.meta)?,
+            errors: parse_raw(self.errors)?,
+            jsonapi: parse_raw(self.jsonapi)?,
+        })
+    }
+}
+
+impl<'a> BorrowedResource<'a> {
+    /// The unparsed JSON value of an attribute
+    pub fn get_attribute_raw(&self, name: &str) -> Option<&'a RawValue> {
+        self.attributes.get(name).cloned()
+    }
+
+    /// Deserialize the value of an attribute, returning `None` if it is not present
+    pub fn get_attribute<T: Deserialize<'a>>(&self, name: &str) -> Result<Option<T>> {
+        match self.get_attribute_raw(name) {
+            Some(raw) => serde_json::from_str(raw.get())
+                .map(Some)
+                .map_err(|e| Error::Conversion {
+                    path: format!("/attributes/{}", pointer_segment(name)),
+                    message: e.to_string(),
+                }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_relationship(&self, name: &str) -> Option<&BorrowedRelationship<'a>> {
+        self.relationships.as_ref().and_then(|relationships| relationships.get(name))
+    }
+
+    /// Convert to the owned `Resource`, parsing the raw values
+    pub fn to_resource(&self) -> Result<Resource> {
+        let mut attributes = ResourceAttributes::new();
+        for (name, raw) in &self.attributes {
+            attributes.insert(name.to_string(), parse_raw(Some(*raw))?.unwrap_or_default());
+        }
+        let relationships = match self.relationships {
+            Some(ref relationships) => {
+                let mut owned = Relationships::new();
+                for (name, relationship) in relationships {
+                    owned.insert(name.to_string(), relationship.to_relationship()?);
+                }
+                Some(owned)
+            }
+            None => None,
+        };
+        Ok(Resource {
+            _type: self._type.to_string(),
+            id: self.id.as_ref().map(|id| id.to_string()),
+            lid: self.lid.as_ref().map(|lid| lid.to_string()),
+            attributes,
+            relationships,
+            links: parse_raw(self.links)?,
+            meta: parse_raw(self.meta)?,
+        })
+    }
+}
+
+impl<'a> BorrowedRelationship<'a> {
+    /// Convert to the owned `Relationship`, parsing the raw values
+    pub fn to_relationship(&self) -> Result<Relationship> {
+        let data = match self.data {
+            None => None,
+            Some(BorrowedIdentifierData::None) => Some(IdentifierData::None),
+            Some(BorrowedIdentifierData::Single(ref identifier)) => {
+                Some(IdentifierData::Single(identifier.to_identifier()?))
+            }
+            Some(BorrowedIdentifierData::Multiple(ref identifiers)) => {
+                let mut owned = ResourceIdentifiers::new();
+                for identifier in identifiers {
+                    owned.push(identifier.to_identifier()?);
+                }
+                Some(IdentifierData::Multiple(owned))
+            }
+        };
+        Ok(Relationship {
+            data,
+            links: parse_raw(self.links)?,
+            meta: parse_raw(self.meta)?,
+        })
+    }
+}
+
+impl<'a> BorrowedIdentifier<'a> {
+    /// Convert to the owned `ResourceIdentifier`
+    pub fn to_identifier(&self) -> Result<ResourceIdentifier> {
+        Ok(ResourceIdentifier {
+            _type: self._type.to_string(),
+            id: self.id.as_ref().map(|id| id.to_string()),
+            lid: self.lid.as_ref().map(|lid| lid.to_string()),
+            meta: parse_raw(self.meta)?,
+        })
+    }
+}
+
+fn to_resources(resources: &[BorrowedResource]) -> Result<Resources> {
+    resources.iter().map(|resource| resource.to_resource()).collect()
+}
+
+fn parse_raw<T: DeserializeOwned>(raw: Option<&RawValue>) -> Result<Option<T>> {
+    match raw {
+        Some(raw) => Ok(serde_json::from_str(raw.get())?),
+        None => Ok(None),
+    }
+}
+
+/// A string borrowed from the input where possible. Serde only borrows a `Cow<str>` that is
+/// deserialized directly, not one inside an `Option` or used as a map key.
+#[derive(Deserialize, PartialEq, Eq, Hash)]
+struct BorrowedStr<'a>(#[serde(borrow)] Cow<'a, str>);
+
+fn borrow_optional_str<'de: 'a, 'a, D>(deserializer: D)
+    -> ::std::result::Result<Option<Cow<'a, str>>, D::Error>
+    where D: Deserializer<'de>
+{
+    Option::<BorrowedStr>::deserialize(deserializer).map(|s| s.map(|s| s.0))
+}
+
+fn borrow_keys<'de: 'a, 'a, D, V>(deserializer: D)
+    -> ::std::result::Result<HashMap<Cow<'a, str>, V>, D::Error>
+    where D: Deserializer<'de>,
+          V: Deserialize<'de>
+{
+    let map = HashMap::<BorrowedStr, V>::deserialize(deserializer)?;
+    Ok(map.into_iter().map(|(key, value)| (key.0, value)).collect())
+}
+
+fn borrow_optional_keys<'de: 'a, 'a, D, V>(deserializer: D)
+    -> ::std::result::Result<Option<HashMap<Cow<'a, str>, V>>, D::Error>
+    where D: Deserializer<'de>,
+          V: Deserialize<'de>
+{
+    let map = Option::<HashMap<BorrowedStr, V>>::deserialize(deserializer)?;
+    Ok(map.map(|map| map.into_iter().map(|(key, value)| (key.0, value)).collect()))
+}
+
+/// `null`, a single value or an array of values
+///
+/// Dispatches on the JSON type, as `#[serde(untagged)]` does not support `RawValue`.
+enum OneOrMany<T> {
+    None,
+    One(T),
+    Many(Vec<T>),
+}
+
+struct OneOrManyVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrManyVisitor<T> {
+    type Value = OneOrMany<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("null, an object or an array")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> ::std::result::Result<Self::Value, E> {
+        Ok(OneOrMany::None)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A)
+        -> ::std::result::Result<Self::Value, A::Error>
+    {
+        T::deserialize(MapAccessDeserializer::new(map)).map(OneOrMany::One)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A)
+        -> ::std::result::Result<Self::Value, A::Error>
+    {
+        Vec::deserialize(SeqAccessDeserializer::new(seq)).map(OneOrMany::Many)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for BorrowedPrimaryData<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+        -> ::std::result::Result<Self, D::Error>
+    {
+        Ok(match deserializer.deserialize_any(OneOrManyVisitor(PhantomData))? {
+            OneOrMany::None => BorrowedPrimaryData::None,
+            OneOrMany::One(resource) => BorrowedPrimaryData::Single(Box::new(resource)),
+            OneOrMany::Many(resources) => BorrowedPrimaryData::Multiple(resources),
+        })
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for BorrowedIdentifierData<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+        -> ::std::result::Result<Self, D::Error>
+    {
+        Ok(match deserializer.deserialize_any(OneOrManyVisitor(PhantomData))? {
+            OneOrMany::None => BorrowedIdentifierData::None,
+            OneOrMany::One(identifier) => BorrowedIdentifierData::Single(identifier),
+            OneOrMany::Many(identifiers) => BorrowedIdentifierData::Multiple(identifiers),
+        })
+    }
+}
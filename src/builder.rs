@@ -0,0 +1,320 @@
+//! Fluent construction of response documents
+//!
+//! ```
+//! use jsonapi::api::*;
+//! use jsonapi::builder::DocumentBuilder;
+//!
+//! let author = Resource {
+//!     _type: "people".into(),
+//!     id: Some("9".into()),
+//!     ..Default::default()
+//! };
+//! let article = Resource {
+//!     _type: "articles".into(),
+//!     id: Some("1".into()),
+//!     ..Default::default()
+//! };
+//!
+//! let doc = DocumentBuilder::new()
+//!     .resource(article)
+//!     .include(author.clone())
+//!     .include(author)
+//!     .link("self", "http://example.com/articles/1")
+//!     .build()
+//!     .unwrap();
+//!
+//! assert_eq!(doc.included.unwrap().len(), 1);
+//! ```
+
+use api::*;
+use errors;
+use model::JsonApiModel;
+use query::Query;
+use std::collections::HashMap;
+
+/// Builds a `JsonApiDocument`, de-duplicating included resources
+///
+/// Sparse copies of the same resource are merged, and included resources that are also
+/// primary resources are left out.
+#[derive(Debug, Default)]
+pub struct DocumentBuilder {
+    data: Option<BuilderData>,
+    included: ResourceSet,
+    links: Option<Links>,
+    meta: Option<Meta>,
+    jsonapi: Option<JsonApiInfo>,
+}
+
+#[derive(Debug)]
+enum BuilderData {
+    Null,
+    Single(Box<Resource>),
+    Multiple(Resources),
+}
+
+impl DocumentBuilder {
+    pub fn new() -> Self {
+        DocumentBuilder::default()
+    }
+
+    /// Use a single resource as primary data
+    pub fn resource(mut self, resource: Resource) -> Self {
+        self.data = Some(BuilderData::Single(Box::new(resource)));
+        self
+    }
+
+    /// Use a collection of resources as primary data
+    pub fn resources(mut self, resources: Resources) -> Self {
+        self.data = Some(BuilderData::Multiple(resources));
+        self
+    }
+
+    /// Use `null` as primary data
+    pub fn null_data(mut self) -> Self {
+        self.data = Some(BuilderData::Null);
+        self
+    }
+
+    /// Use a model as primary data, including its related resources
+    ///
+    /// Panics if the model can not be serialized, see `try_model`
+    pub fn model<M: JsonApiModel>(self, model: &M) -> Self {
+        self.model_with_query(model, &Default::default())
+    }
+
+    /// Use a model as primary data, with fields and included resources selected by `query`
+    ///
+    /// Panics if the model can not be serialized, see `try_model_with_query`
+    pub fn model_with_query<M: JsonApiModel>(self, model: &M, query: &Query) -> Self {
+        self.try_model_with_query(model, query).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Use a model as primary data, including its related resources, failing if it can not be
+    /// serialized
+    pub fn try_model<M: JsonApiModel>(self, model: &M) -> errors::Result<Self> {
+        self.try_model_with_query(model, &Default::default())
+    }
+
+    /// Use a model as primary data, with fields and included resources selected by `query`,
+    /// failing if it can not be serialized
+    pub fn try_model_with_query<M: JsonApiModel>(self, model: &M, query: &Query)
+        -> errors::Result<Self>
+    {
+        let (resource, included) = model.try_to_jsonapi_resource_with_query(query)?;
+        Ok(self.resource(resource).include_all(included.unwrap_or_default()))
+    }
+
+    /// Use a collection of models as primary data, including their related resources
+    ///
+    /// Panics if a model can not be serialized, see `try_models`
+    pub fn models<M: JsonApiModel>(self, models: &[M]) -> Self {
+        self.models_with_query(models, &Default::default())
+    }
+
+    /// Use a collection of models as primary data, with fields and included resources selected
+    /// by `query`
+    ///
+    /// Panics if a model can not be serialized, see `try_models_with_query`
+    pub fn models_with_query<M: JsonApiModel>(self, models: &[M], query: &Query) -> Self {
+        self.try_models_with_query(models, query).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Use a collection of models as primary data, including their related resources, failing
+    /// if a model can not be serialized
+    pub fn try_models<M: JsonApiModel>(self, models: &[M]) -> errors::Result<Self> {
+        self.try_models_with_query(models, &Default::default())
+    }
+
+    /// Use a collection of models as primary data, with fields and included resources selected
+    /// by `query`, failing if a model can not be serialized
+    pub fn try_models_with_query<M: JsonApiModel>(mut self, models: &[M], query: &Query)
+        -> errors::Result<Self>
+    {
+        let mut resources = Resources::new();
+        for model in models {
+            let (resource, included) = model.try_to_jsonapi_resource_with_query(query)?;
+            resources.push(resource);
+            self = self.include_all(included.unwrap_or_default());
+        }
+        Ok(self.resources(resources))
+    }
+
+    /// Add a resource to `included`
+    pub fn include(mut self, resource: Resource) -> Self {
+        self.included.insert(resource);
+        self
+    }
+
+    /// Add resources to `included`
+    pub fn include_all(mut self, resources: Resources) -> Self {
+        for resource in resources {
+            self.included.insert(resource);
+        }
+        self
+    }
+
+    /// Add a top-level link
+    pub fn link<L: Into<Link>>(mut self, name: &str, link: L) -> Self {
+        self.links.get_or_insert_with(Links::new).insert(name.into(), link.into());
+        self
+    }
+
+    /// Set the top-level links, replacing any added before
+    pub fn links(mut self, links: Links) -> Self {
+        self.links = Some(links);
+        self
+    }
+
+    /// Add a top-level meta member
+    pub fn meta_member(mut self, name: &str, value: JsonApiValue) -> Self {
+        self.meta.get_or_insert_with(Meta::new).insert(name.into(), value);
+        self
+    }
+
+    /// Set the top-level meta, replacing any added before
+    pub fn meta(mut self, meta: Meta) -> Self {
+        self.meta = Some(meta);
+        self
+    }
+
+    pub fn jsonapi(mut self, jsonapi: JsonApiInfo) -> Self {
+        self.jsonapi = Some(jsonapi);
+        self
+    }
+
+    /// Build the document, or return the specification violations it would contain as a
+    /// response, see `JsonApiDocument::validate_response`
+    pub fn build(self) -> Result<JsonApiDocument, Vec<DocumentValidationError>> {
+        let mut included = self.included;
+        let data = match self.data {
+            None => None,
+            Some(BuilderData::Null) => Some(PrimaryData::None),
+            Some(BuilderData::Single(resource)) => {
+                included.remove(&resource);
+                Some(PrimaryData::Single(resource))
+            }
+            Some(BuilderData::Multiple(resources)) => {
+                for resource in &resources {
+                    included.remove(resource);
+                }
+                Some(PrimaryData::Multiple(resources))
+            }
+        };
+
+        let included = included.into_resources();
+        let doc = JsonApiDocument {
+            data,
+            included: if included.is_empty() { None } else { Some(included) },
+            links: self.links,
+            meta: self.meta,
+            jsonapi: self.jsonapi,
+            ..Default::default()
+        };
+
+        match doc.validate_response() {
+            None => Ok(doc),
+            Some(errors) => Err(errors),
+        }
+    }
+}
+
+/// Key identifying a resource by type and id, or by type and local id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum ResourceKey {
+    Id(String, String),
+    Lid(String, String),
+}
+
+impl ResourceKey {
+    /// The keys of a resource, one for its id and one for its lid where it has them. Two
+    /// copies of a resource are the same resource when they share any key.
+    pub(crate) fn all(resource: &Resource) -> Vec<ResourceKey> {
+        let id = resource.id
+            .as_ref()
+            .map(|id| ResourceKey::Id(resource._type.clone(), id.clone()));
+        let lid = resource.lid
+            .as_ref()
+            .map(|lid| ResourceKey::Lid(resource._type.clone(), lid.clone()));
+        id.into_iter().chain(lid).collect()
+    }
+}
+
+/// Ordered set of resources, merging sparse copies of the same resource
+///
+/// Removed resources leave an empty slot behind, so that later copies of them are dropped too.
+#[derive(Debug, Default)]
+pub(crate) struct ResourceSet {
+    resources: Vec<Option<Resource>>,
+    positions: HashMap<ResourceKey, usize>,
+}
+
+impl ResourceSet {
+    /// Add a resource, merging it into an earlier copy. Returns `true` if it was new.
+    pub(crate) fn insert(&mut self, resource: Resource) -> bool {
+        let keys = ResourceKey::all(&resource);
+        match self.position(&keys) {
+            Some(position) => {
+                if let Some(ref mut target) = self.resources[position] {
+                    merge_resource(target, resource);
+                }
+                for key in keys {
+                    self.positions.entry(key).or_insert(position);
+                }
+                false
+            }
+            None => {
+                for key in keys {
+                    self.positions.insert(key, self.resources.len());
+                }
+                self.resources.push(Some(resource));
+                true
+            }
+        }
+    }
+
+    /// Remove the copy of `resource`, if any
+    pub(crate) fn remove(&mut self, resource: &Resource) {
+        if let Some(position) = self.position(&ResourceKey::all(resource)) {
+            self.resources[position] = None;
+        }
+    }
+
+    pub(crate) fn into_resources(self) -> Resources {
+        self.resources.into_iter().flatten().collect()
+    }
+
+    fn position(&self, keys: &[ResourceKey]) -> Option<usize> {
+        keys.iter().filter_map(|key| self.positions.get(key)).next().cloned()
+    }
+}
+
+/// Merge a sparse copy of a resource into `target`, keeping the values already present
+fn merge_resource(target: &mut Resource, other: Resource) {
+    if target.id.is_none() {
+        target.id = other.id;
+    }
+    if target.lid.is_none() {
+        target.lid = other.lid;
+    }
+    for (name, value) in other.attributes {
+        target.attributes.entry(name).or_insert(value);
+    }
+    if let Some(relationships) = other.relationships {
+        let target_relationships = target.relationships.get_or_insert_with(Relationships::new);
+        for (name, relationship) in relationships {
+            target_relationships.entry(name).or_insert(relationship);
+        }
+    }
+    if let Some(links) = other.links {
+        let target_links = target.links.get_or_insert_with(Links::new);
+        for (name, link) in links.0 {
+            target_links.entry(name).or_insert(link);
+        }
+    }
+    if let Some(meta) = other.meta {
+        let target_meta = target.meta.get_or_insert_with(Meta::new);
+        for (name, value) in meta {
+            target_meta.entry(name).or_insert(value);
+        }
+    }
+}
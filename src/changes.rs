@@ -0,0 +1,122 @@
+//! Changes between two snapshots of a document
+//!
+//! `JsonApiDocument::diff` reports the resources added, removed or changed between two documents.
+//!
+//! ```
+//! use jsonapi::api::JsonApiDocument;
+//! use std::str::FromStr;
+//!
+//! let yesterday = JsonApiDocument::from_str(r#"{
+//!   "data": [
+//!     { "type": "articles", "id": "1", "attributes": { "title": "Rails is Omakase" } },
+//!     { "type": "articles", "id": "2", "attributes": { "title": "JSON:API paints my bikeshed" } }
+//!   ]
+//! }"#).unwrap();
+//! let today = JsonApiDocument::from_str(r#"{
+//!   "data": [
+//!     { "type": "articles", "id": "1", "attributes": { "title": "Rails was Omakase" } },
+//!     { "type": "articles", "id": "3", "attributes": { "title": "Rust is fast" } }
+//!   ]
+//! }"#).unwrap();
+//!
+//! let changes = yesterday.diff(&today);
+//! assert_eq!(changes.added[0].id, Some("3".into()));
+//! assert_eq!(changes.removed[0].id, Some("2".into()));
+//! assert_eq!(changes.changed[0].resource_id, "1");
+//! ```
+
+use api::*;
+use std::collections::BTreeMap;
+
+/// Resources added, removed and changed between two documents, each sorted by `(type, id)`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DocumentDiff {
+    /// Resources only in the newer document
+    pub added: Vec<Resource>,
+    /// Resources only in the older document
+    pub removed: Vec<Resource>,
+    /// The changes to the attributes and relationships of resources in both documents
+    pub changed: Vec<PatchSet>,
+    /// Resources in both documents that could not be diffed, with the reason
+    pub failed: Vec<(ResourceIdentifier, DiffPatchError)>,
+}
+
+impl DocumentDiff {
+    /// Whether the documents have the same resources
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() &&
+        self.failed.is_empty()
+    }
+}
+
+impl JsonApiDocument {
+    /// The resources added, removed and changed in `other`, with changes as from
+    /// `Resource::diff`
+    ///
+    /// Resources are matched by `(type, id)` across `data` and `included`; resources without an
+    /// `id` are left out. Changes to `meta` and `links` are not reported.
+    pub fn diff(&self, other: &JsonApiDocument) -> DocumentDiff {
+        self.diff_with(other, false)
+    }
+
+    /// The resources added, removed and changed in `other`, with changes to nested attribute
+    /// values as from `Resource::diff_deep`
+    pub fn diff_deep(&self, other: &JsonApiDocument) -> DocumentDiff {
+        self.diff_with(other, true)
+    }
+
+    fn diff_with(&self, other: &JsonApiDocument, deep: bool) -> DocumentDiff {
+        let previous = resources_by_id(self);
+        let next = resources_by_id(other);
+        let mut diff = DocumentDiff::default();
+
+        for (key, resource) in &previous {
+            match next.get(key) {
+                None => diff.removed.push((*resource).clone()),
+                Some(next) => {
+                    let patchset = if deep {
+                        resource.diff_deep((*next).clone())
+                    } else {
+                        resource.diff((*next).clone())
+                    };
+                    match patchset {
+                        Ok(ref patchset) if patchset.patches.is_empty() => {}
+                        Ok(patchset) => diff.changed.push(patchset),
+                        Err(error) => {
+                            let identifier = ResourceIdentifier {
+                                _type: key.0.to_string(),
+                                id: Some(key.1.to_string()),
+                                ..Default::default()
+                            };
+                            diff.failed.push((identifier, error));
+                        }
+                    }
+                }
+            }
+        }
+        for (key, resource) in &next {
+            if !previous.contains_key(key) {
+                diff.added.push((*resource).clone());
+            }
+        }
+
+        diff
+    }
+}
+
+fn resources_by_id(doc: &JsonApiDocument) -> BTreeMap<(&str, &str), &Resource> {
+    let primary: &[Resource] = match doc.data {
+        Some(PrimaryData::Single(ref resource)) => std::slice::from_ref(resource),
+        Some(PrimaryData::Multiple(ref data)) => data,
+        Some(PrimaryData::None) | None => &[],
+    };
+    let included = doc.included.as_ref().map_or(&[][..], |included| &included[..]);
+
+    let mut resources = BTreeMap::new();
+    for resource in primary.iter().chain(included) {
+        if let Some(ref id) = resource.id {
+            resources.entry((resource._type.as_str(), id.as_str())).or_insert(resource);
+        }
+    }
+    resources
+}
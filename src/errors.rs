@@ -1,8 +1,253 @@
-error_chain!{
-    errors {
-        ResourceToModelError(t: String) {
-            description("Error converting Resource to Model")
-            display("Error converting Resource to Model: '{}'", t)
+//! The error type of this crate
+//!
+//! ```
+//! use jsonapi::api::JsonApiDocument;
+//! use jsonapi::errors::Error;
+//! use std::str::FromStr;
+//!
+//! match JsonApiDocument::from_str("{\n  \"data\": [,]\n}") {
+//!     Err(error @ Error::Parse { .. }) => {
+//!         assert!(matches!(error, Error::Parse { line: 2, column: 12, .. }));
+//!         assert_eq!(error.to_jsonapi_error().status, Some("400".into()));
+//!     }
+//!     _ => panic!("expected a parse error"),
+//! }
+//! ```
+
+use api::{DocumentValidationError, ErrorSource, JsonApiDocument, JsonApiError};
+use serde_json;
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The input is not valid JSON or does not have the structure of the expected value
+    Parse {
+        message: String,
+        /// The line of the input, starting at 1, or 0 when the input was not text
+        line: usize,
+        /// The column on that line, starting at 1
+        column: usize,
+    },
+    /// A resource or one of its values could not be converted to or from a model
+    Conversion {
+        /// JSON pointer within the primary data, such as `/attributes/title`
+        path: String,
+        message: String,
+    },
+    /// The document has no primary data
+    MissingData,
+    /// A resource or model has neither an `id` nor a `lid` where one is needed, such as for a
+    /// resource identifier or a `PatchSet`
+    MissingId {
+        resource_type: String,
+    },
+    /// A model could not be serialized to a resource object
+    Serialization {
+        resource_type: String,
+        message: String,
+    },
+    /// The document does not follow the JSON:API specification
+    InvalidDocument {
+        /// JSON pointer to the offending member of the document
+        pointer: String,
+        message: String,
+    },
+    /// A patch could not be applied because the resource does not have the value it changes
+    Patch {
+        /// The subject of the patch, see `api::Patch`
+        subject: String,
+        message: String,
+    },
+    /// A patch is malformed, such as a subject that is not a valid path or a value of the wrong
+    /// type
+    InvalidPatch {
+        /// The subject of the patch, see `api::Patch`
+        subject: String,
+        message: String,
+    },
+    /// Reading or writing a document failed
+    Io(io::Error),
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+impl Error {
+    /// The HTTP status code for this error
+    pub fn status(&self) -> u16 {
+        match *self {
+            Error::Parse { .. } | Error::MissingData | Error::InvalidDocument { .. } |
+            Error::InvalidPatch { .. } => 400,
+            Error::Patch { .. } => 409,
+            Error::Conversion { .. } => 422,
+            Error::MissingId { .. } | Error::Serialization { .. } | Error::Io(_) => 500,
+        }
+    }
+
+    /// A short name for the kind of error, used as the `code` of a `JsonApiError`
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Error::Parse { .. } => "parse_error",
+            Error::Conversion { .. } => "conversion_error",
+            Error::MissingData => "missing_data",
+            Error::MissingId { .. } => "missing_id",
+            Error::Serialization { .. } => "serialization_error",
+            Error::InvalidDocument { .. } => "invalid_document",
+            Error::Patch { .. } => "patch_conflict",
+            Error::InvalidPatch { .. } => "invalid_patch",
+            Error::Io(_) => "io_error",
+        }
+    }
+
+    /// A `JsonApiError` describing this error, for use in an error document
+    pub fn to_jsonapi_error(&self) -> JsonApiError {
+        let (title, pointer) = match *self {
+            Error::Parse { .. } => ("Invalid JSON", None),
+            Error::Conversion { ref path, .. } => {
+                ("Invalid resource", Some(format!("/data{}", path)))
+            }
+            Error::MissingData => ("Missing primary data", Some("".to_string())),
+            Error::InvalidDocument { ref pointer, .. } => {
+                ("Invalid document", Some(pointer.clone()))
+            }
+            Error::Patch { .. } => ("Conflicting change", None),
+            Error::InvalidPatch { .. } => ("Invalid patch", None),
+            Error::MissingId { .. } | Error::Serialization { .. } | Error::Io(_) => {
+                ("Internal Server Error", None)
+            }
+        };
+        JsonApiError {
+            status: Some(self.status().to_string()),
+            code: Some(self.code().into()),
+            title: Some(title.into()),
+            detail: Some(self.to_string()),
+            source: pointer.map(|pointer| ErrorSource { pointer: Some(pointer), parameter: None }),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Parse { ref message, line: 0, .. } => {
+                write!(f, "Error parsing document: {}", message)
+            }
+            Error::Parse { ref message, line, column } => {
+                write!(f, "Error parsing document: {} at line {} column {}", message, line,
+                       column)
+            }
+            Error::Conversion { ref path, ref message } if path.is_empty() => {
+                write!(f, "Error converting resource: {}", message)
+            }
+            Error::Conversion { ref path, ref message } => {
+                write!(f, "Error converting resource at '{}': {}", path, message)
+            }
+            Error::MissingData => write!(f, "Document had no data"),
+            Error::MissingId { ref resource_type } => {
+                write!(f, "Resource of type '{}' has no id", resource_type)
+            }
+            Error::Serialization { ref resource_type, ref message } => {
+                write!(f, "Error serializing '{}': {}", resource_type, message)
+            }
+            Error::InvalidDocument { ref pointer, ref message } => {
+                write!(f, "Invalid document at '{}': {}", pointer, message)
+            }
+            Error::Patch { ref subject, ref message } => {
+                write!(f, "Error patching '{}': {}", subject, message)
+            }
+            Error::InvalidPatch { ref subject, ref message } => {
+                write!(f, "Invalid patch of '{}': {}", subject, message)
+            }
+            Error::Io(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref error) => Some(error),
+            _ => None,
         }
     }
 }
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// Parse errors keep the line and column of `serde_json`, I/O errors become `Error::Io`
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        if error.is_io() {
+            return Error::Io(error.into());
+        }
+        let (line, column) = (error.line(), error.column());
+        let message = error.to_string();
+        let location = format!(" at line {} column {}", line, column);
+        let message = match message.strip_suffix(&location) {
+            Some(message) => message.to_string(),
+            None => message,
+        };
+        Error::Parse { message, line, column }
+    }
+}
+
+/// An error document with a `JsonApiError` for each error, such as the problems reported by
+/// `JsonApiModel::from_jsonapi_document_checked`
+///
+/// Each error keeps its own `status`: `422` for conversion errors, but `400` for
+/// `Error::MissingData`. Respond with the status shared by the errors, or `400` when they differ.
+///
+/// ```
+/// # #[macro_use] extern crate jsonapi;
+/// # #[macro_use] extern crate serde_derive;
+/// use jsonapi::errors::error_document;
+/// use jsonapi::model::*;
+/// use std::str::FromStr;
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Dog {
+///     id: Option<String>,
+///     name: String,
+///     age: u32,
+/// }
+/// jsonapi_model!(Dog; "dogs");
+///
+/// # fn main() {
+/// let doc = JsonApiDocument::from_str(r#"{
+///   "data": { "type": "dogs", "attributes": { "name": 1, "age": "two" } }
+/// }"#).unwrap();
+///
+/// let errors = Dog::from_jsonapi_document_checked(&doc).unwrap_err();
+/// let doc = error_document(&errors);
+/// let pointers: Vec<String> = doc.errors.unwrap().into_iter()
+///     .map(|error| error.source.unwrap().pointer.unwrap())
+///     .collect();
+/// assert_eq!(pointers.len(), 2);
+/// assert!(pointers.contains(&"/data/attributes/age".to_string()));
+/// assert!(pointers.contains(&"/data/attributes/name".to_string()));
+/// # }
+/// ```
+pub fn error_document(errors: &[Error]) -> JsonApiDocument {
+    JsonApiDocument {
+        errors: Some(errors.iter().map(Error::to_jsonapi_error).collect()),
+        ..Default::default()
+    }
+}
+
+impl From<DocumentValidationError> for Error {
+    fn from(error: DocumentValidationError) -> Self {
+        Error::InvalidDocument { pointer: error.pointer().into(), message: error.to_string() }
+    }
+}
+
+impl From<Error> for JsonApiError {
+    fn from(error: Error) -> Self {
+        error.to_jsonapi_error()
+    }
+}
@@ -0,0 +1,207 @@
+//! Indexed, read-only view of a compound document
+//!
+//! A `DocumentGraph` indexes `data` and `included` by `(type, id)` and `(type, lid)`.
+//!
+//! ```
+//! use jsonapi::api::JsonApiDocument;
+//! use jsonapi::graph::DocumentGraph;
+//! use std::str::FromStr;
+//!
+//! let doc = JsonApiDocument::from_str(r#"{
+//!   "data": {
+//!     "type": "articles", "id": "1",
+//!     "relationships": {
+//!       "comments": { "data": [{ "type": "comments", "id": "5" }] }
+//!     }
+//!   },
+//!   "included": [{
+//!     "type": "comments", "id": "5",
+//!     "relationships": { "author": { "data": { "type": "people", "id": "9" } } }
+//!   }, {
+//!     "type": "people", "id": "9", "attributes": { "name": "Dan" }
+//!   }]
+//! }"#).unwrap();
+//!
+//! let graph = DocumentGraph::new(&doc);
+//! let article = graph.primary()[0];
+//! assert_eq!(graph.related(article, "comments")[0].id, Some("5".into()));
+//! assert_eq!(graph.walk("comments.author")[0].get_attribute("name").unwrap(), "Dan");
+//! ```
+
+use api::*;
+use std::collections::{HashMap, HashSet};
+
+/// Read-only index over the resources of a `JsonApiDocument`
+#[derive(Debug)]
+pub struct DocumentGraph<'a> {
+    /// Primary resources followed by included resources, in document order
+    resources: Vec<&'a Resource>,
+    primary_len: usize,
+    by_id: HashMap<(&'a str, &'a str), usize>,
+    by_lid: HashMap<(&'a str, &'a str), usize>,
+    by_type: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> DocumentGraph<'a> {
+    /// Index the resources of `doc`. When a `(type, id)` pair occurs more than once, the first
+    /// occurrence is used.
+    pub fn new(doc: &'a JsonApiDocument) -> Self {
+        let mut resources: Vec<&'a Resource> = Vec::new();
+        match doc.data {
+            Some(PrimaryData::Single(ref resource)) => resources.push(resource),
+            Some(PrimaryData::Multiple(ref data)) => resources.extend(data.iter()),
+            Some(PrimaryData::None) | None => {}
+        }
+        let primary_len = resources.len();
+        if let Some(ref included) = doc.included {
+            resources.extend(included.iter());
+        }
+
+        let mut graph = DocumentGraph {
+            resources,
+            primary_len,
+            by_id: HashMap::new(),
+            by_lid: HashMap::new(),
+            by_type: HashMap::new(),
+        };
+
+        for (index, resource) in graph.resources.iter().enumerate() {
+            if let Some(ref id) = resource.id {
+                graph.by_id.entry((&resource._type, id)).or_insert(index);
+            }
+            if let Some(ref lid) = resource.lid {
+                graph.by_lid.entry((&resource._type, lid)).or_insert(index);
+            }
+            graph.by_type.entry(&resource._type).or_default().push(index);
+        }
+
+        graph
+    }
+
+    /// The resources in `data`
+    pub fn primary(&self) -> &[&'a Resource] {
+        &self.resources[..self.primary_len]
+    }
+
+    /// The resources in `included`
+    pub fn included(&self) -> &[&'a Resource] {
+        &self.resources[self.primary_len..]
+    }
+
+    /// Number of resources in `data` and `included`
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Find a resource by `type` and `id`
+    pub fn find(&self, _type: &str, id: &str) -> Option<&'a Resource> {
+        self.by_id.get(&(_type, id)).map(|&index| self.resources[index])
+    }
+
+    /// Find the resource an identifier refers to, by `id` or by `lid`
+    pub fn get(&self, identifier: &ResourceIdentifier) -> Option<&'a Resource> {
+        self.index_of(identifier).map(|index| self.resources[index])
+    }
+
+    /// All resources of a type, primary resources first
+    pub fn resources_of_type<'b>(&'b self, _type: &str)
+        -> impl Iterator<Item = &'a Resource> + 'b
+    {
+        self.by_type
+            .get(_type)
+            .map(|indices| indices.as_slice())
+            .unwrap_or(&[])
+            .iter()
+            .map(move |&index| self.resources[index])
+    }
+
+    /// The resources linked from `resource` through the named relationship that are present in
+    /// the document. Identifiers that resolve to nothing are skipped.
+    pub fn related(&self, resource: &Resource, relationship: &str) -> Vec<&'a Resource> {
+        self.related_indices(resource, relationship)
+            .into_iter()
+            .map(|index| self.resources[index])
+            .collect()
+    }
+
+    /// Follow a dot-separated include path, such as `comments.author`, from the primary data.
+    ///
+    /// An empty path returns the primary data.
+    pub fn walk(&self, path: &str) -> Vec<&'a Resource> {
+        self.walk_indices((0..self.primary_len).collect(), path)
+            .into_iter()
+            .map(|index| self.resources[index])
+            .collect()
+    }
+
+    /// Follow a dot-separated include path from `resource`, see `walk`. An empty path returns
+    /// `resource` itself.
+    pub fn walk_from(&self, resource: &'a Resource, path: &str) -> Vec<&'a Resource> {
+        let path = path.trim_start_matches('.');
+        if path.is_empty() {
+            return vec![resource];
+        }
+        let mut segments = path.splitn(2, '.');
+        let start = self.related_indices(resource, segments.next().unwrap_or(""));
+        self.walk_indices(start, segments.next().unwrap_or(""))
+            .into_iter()
+            .map(|index| self.resources[index])
+            .collect()
+    }
+
+    /// Position of the resource an identifier refers to, in `data` followed by `included`
+    pub(crate) fn index_of(&self, identifier: &ResourceIdentifier) -> Option<usize> {
+        let by_id = identifier.id
+            .as_ref()
+            .and_then(|id| self.by_id.get(&(identifier._type.as_str(), id.as_str())));
+        let by_lid = identifier.lid
+            .as_ref()
+            .and_then(|lid| self.by_lid.get(&(identifier._type.as_str(), lid.as_str())));
+        by_id.or(by_lid).cloned()
+    }
+
+    pub(crate) fn resource_at(&self, index: usize) -> &'a Resource {
+        self.resources[index]
+    }
+
+    pub(crate) fn primary_len(&self) -> usize {
+        self.primary_len
+    }
+
+    fn related_indices(&self, resource: &Resource, relationship: &str) -> Vec<usize> {
+        let data = match resource.get_relationship(relationship) {
+            Some(&Relationship { data: Some(ref data), .. }) => data,
+            _ => return Vec::new(),
+        };
+        match *data {
+            IdentifierData::None => Vec::new(),
+            IdentifierData::Single(ref identifier) => {
+                self.index_of(identifier).into_iter().collect()
+            }
+            IdentifierData::Multiple(ref identifiers) => {
+                identifiers.iter().filter_map(|identifier| self.index_of(identifier)).collect()
+            }
+        }
+    }
+
+    fn walk_indices(&self, start: Vec<usize>, path: &str) -> Vec<usize> {
+        let mut current = start;
+        for relationship in path.split('.').filter(|s| !s.is_empty()) {
+            let mut seen = HashSet::new();
+            let mut next = Vec::new();
+            for index in current {
+                for related in self.related_indices(self.resources[index], relationship) {
+                    if seen.insert(related) {
+                        next.push(related);
+                    }
+                }
+            }
+            current = next;
+        }
+        current
+    }
+}
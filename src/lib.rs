@@ -22,9 +22,17 @@ extern crate queryst;
 
 #[macro_use] extern crate log;
 
-#[macro_use] extern crate error_chain;
-
 pub mod api;
 pub mod query;
 pub mod model;
 pub mod errors;
+pub mod negotiation;
+pub mod atomic;
+pub mod linkage;
+pub mod graph;
+pub mod builder;
+pub mod stream;
+pub mod borrowed;
+pub mod patch;
+pub mod merge;
+pub mod changes;
@@ -0,0 +1,149 @@
+//! Full linkage analysis of compound documents
+//!
+//! `JsonApiDocument::linkage` reports included resources not reachable from the primary data
+//! (orphans), and resource identifiers that refer to no resource in the document.
+
+use api::*;
+use graph::DocumentGraph;
+use std::collections::HashSet;
+
+/// Result of `JsonApiDocument::linkage`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Linkage {
+    /// JSON pointers to included resources that can not be reached from the primary data
+    pub orphans: Vec<String>,
+    /// Identifiers that refer to no resource in `data` or `included`, each with the JSON
+    /// pointer locating it
+    pub dangling: Vec<(String, ResourceIdentifier)>,
+}
+
+impl Linkage {
+    /// Returns `true` if every included resource is reachable from the primary data
+    pub fn is_fully_linked(&self) -> bool {
+        self.orphans.is_empty()
+    }
+}
+
+/// The identifiers in the relationships of `resource`, with their pointers relative to it
+fn linked_identifiers(resource: &Resource) -> Vec<(String, &ResourceIdentifier)> {
+    let mut linked = Vec::new();
+    if let Some(ref relationships) = resource.relationships {
+        let mut names: Vec<&String> = relationships.keys().collect();
+        names.sort();
+        for name in names {
+            let pointer = format!("/relationships/{}/data", pointer_segment(name));
+            match relationships[name].data {
+                Some(IdentifierData::Single(ref identifier)) => {
+                    linked.push((pointer, identifier))
+                }
+                Some(IdentifierData::Multiple(ref identifiers)) => {
+                    for (index, identifier) in identifiers.iter().enumerate() {
+                        linked.push((format!("{}/{}", pointer, index), identifier));
+                    }
+                }
+                Some(IdentifierData::None) | None => {}
+            }
+        }
+    }
+    linked
+}
+
+impl JsonApiDocument {
+    /// Analyse the relationship linkage between the primary data and the included resources
+    ///
+    /// ```
+    /// use jsonapi::api::JsonApiDocument;
+    /// use std::str::FromStr;
+    ///
+    /// let doc = JsonApiDocument::from_str(r#"{
+    ///   "data": {
+    ///     "type": "articles", "id": "1",
+    ///     "relationships": {
+    ///       "author": { "data": { "type": "people", "id": "9" } },
+    ///       "editor": { "data": { "type": "people", "id": "2" } }
+    ///     }
+    ///   },
+    ///   "included": [
+    ///     { "type": "people", "id": "9" },
+    ///     { "type": "people", "id": "5" }
+    ///   ]
+    /// }"#).unwrap();
+    ///
+    /// let linkage = doc.linkage();
+    /// assert_eq!(linkage.orphans, vec!["/included/1".to_string()]);
+    /// assert_eq!(linkage.dangling[0].0, "/data/relationships/editor/data");
+    /// ```
+    pub fn linkage(&self) -> Linkage {
+        let graph = DocumentGraph::new(self);
+        let single = matches!(self.data, Some(PrimaryData::Single(_)));
+        let pointer = |index: usize| {
+            if index >= graph.primary_len() {
+                format!("/included/{}", index - graph.primary_len())
+            } else if single {
+                "/data".to_string()
+            } else {
+                format!("/data/{}", index)
+            }
+        };
+
+        let mut linkage = Linkage::default();
+
+        for index in 0..graph.len() {
+            for (relative, identifier) in linked_identifiers(graph.resource_at(index)) {
+                if graph.index_of(identifier).is_none() {
+                    linkage.dangling.push((format!("{}{}", pointer(index), relative),
+                                           identifier.clone()));
+                }
+            }
+        }
+
+        let mut reached = HashSet::new();
+        let mut pending: Vec<usize> = (0..graph.primary_len()).collect();
+
+        while let Some(index) = pending.pop() {
+            if !reached.insert(index) {
+                continue;
+            }
+            for (_, identifier) in linked_identifiers(graph.resource_at(index)) {
+                if let Some(found) = graph.index_of(identifier) {
+                    if !reached.contains(&found) {
+                        pending.push(found);
+                    }
+                }
+            }
+        }
+
+        for index in graph.primary_len()..graph.len() {
+            if !reached.contains(&index) {
+                linkage.orphans.push(pointer(index));
+            }
+        }
+
+        linkage
+    }
+
+    /// Remove the included resources that can not be reached from the primary data, returning
+    /// them. `included` is dropped altogether when no resources remain in it.
+    pub fn prune_orphans(&mut self) -> Resources {
+        let orphans: HashSet<String> = self.linkage().orphans.into_iter().collect();
+        if orphans.is_empty() {
+            return Resources::new();
+        }
+
+        let mut pruned = Resources::new();
+        if let Some(included) = self.included.take() {
+            let mut kept = Resources::new();
+            for (index, resource) in included.into_iter().enumerate() {
+                if orphans.contains(&format!("/included/{}", index)) {
+                    pruned.push(resource);
+                } else {
+                    kept.push(resource);
+                }
+            }
+            if !kept.is_empty() {
+                self.included = Some(kept);
+            }
+        }
+        pruned
+    }
+}
@@ -0,0 +1,206 @@
+//! Three-way merge of `PatchSet`s made against the same resource
+//!
+//! Patches touching overlapping values are reported as a `Conflict`, which can be resolved
+//! with a closure.
+//!
+//! ```
+//! use jsonapi::api::*;
+//! use jsonapi::merge::{MergeError, Resolution};
+//! use std::str::FromStr;
+//!
+//! let base = Resource::from_str(r#"{
+//!   "type": "articles", "id": "1",
+//!   "attributes": { "title": "Rails is Omakase", "likes": 250 }
+//! }"#).unwrap();
+//!
+//! let mut ours = base.clone();
+//! ours.attributes.insert("title".into(), "Rails was Omakase".into());
+//! let mut theirs = base.clone();
+//! theirs.attributes.insert("title".into(), "Rails is not Omakase".into());
+//! theirs.attributes.insert("likes".into(), 251.into());
+//!
+//! let ours = base.diff(ours).unwrap();
+//! let theirs = base.diff(theirs).unwrap();
+//!
+//! match ours.merge(&theirs) {
+//!     Err(MergeError::Conflicts(conflicts)) => assert_eq!(conflicts[0].name, "title"),
+//!     _ => panic!("expected a conflict"),
+//! }
+//!
+//! let merged = ours.merge_with(&theirs, |_| Resolution::Ours).unwrap();
+//! let merged = base.patch_strict(merged).unwrap();
+//! assert_eq!(merged.get_attribute("title").unwrap(), "Rails was Omakase");
+//! assert_eq!(merged.get_attribute("likes").unwrap(), 251);
+//! ```
+
+use api::*;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Overlapping changes by both sides to one attribute or relationship
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub patch_type: PatchType,
+    /// The attribute or relationship name
+    pub name: String,
+    /// All patches of our side for the attribute or relationship
+    pub ours: Vec<Patch>,
+    /// All patches of their side for the attribute or relationship
+    pub theirs: Vec<Patch>,
+}
+
+/// How to resolve a `Conflict`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Keep our patches
+    Ours,
+    /// Keep their patches
+    Theirs,
+    /// Keep neither, leaving the attribute or relationship as it is in the base resource
+    Neither,
+    /// Use these patches instead
+    Use(Vec<Patch>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+    /// The patch sets are for different resources, given as `type/id`
+    DifferentResources(String, String),
+    /// Changes both sides made to the same values
+    Conflicts(Vec<Conflict>),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeError::DifferentResources(ref ours, ref theirs) => {
+                write!(f, "can not merge changes to '{}' with changes to '{}'", ours, theirs)
+            }
+            MergeError::Conflicts(ref conflicts) => {
+                let names: Vec<&str> = conflicts.iter().map(|c| c.name.as_str()).collect();
+                write!(f, "conflicting changes to {}", names.join(", "))
+            }
+        }
+    }
+}
+
+impl PatchSet {
+    /// Merge with another patch set made against the same base resource, failing with all
+    /// conflicts if there are any
+    pub fn merge(&self, theirs: &PatchSet) -> Result<PatchSet, MergeError> {
+        let mut conflicts = Vec::new();
+        let merged = self.merge_with(theirs, |conflict| {
+            conflicts.push(conflict.clone());
+            Resolution::Neither
+        })?;
+        if conflicts.is_empty() {
+            Ok(merged)
+        } else {
+            Err(MergeError::Conflicts(conflicts))
+        }
+    }
+
+    /// Merge with another patch set made against the same base resource, calling `resolve`
+    /// for each conflict
+    pub fn merge_with<F>(&self, theirs: &PatchSet, mut resolve: F) -> Result<PatchSet, MergeError>
+        where F: FnMut(&Conflict) -> Resolution
+    {
+        if self.resource_type != theirs.resource_type || self.resource_id != theirs.resource_id {
+            return Err(MergeError::DifferentResources(
+                format!("{}/{}", self.resource_type, self.resource_id),
+                format!("{}/{}", theirs.resource_type, theirs.resource_id)));
+        }
+
+        let mut merged = PatchSet {
+            resource_type: self.resource_type.clone(),
+            resource_id: self.resource_id.clone(),
+            patches: Vec::new(),
+        };
+
+        let mut groups: Vec<(PatchType, &str)> = self.patches
+            .iter()
+            .chain(theirs.patches.iter())
+            .map(|patch| (patch.patch_type, top_level_name(patch)))
+            .collect();
+        groups.sort_by(|a, b| (a.0 as u8, a.1).cmp(&(b.0 as u8, b.1)));
+        groups.dedup();
+
+        for (patch_type, name) in groups {
+            let in_group = |patch: &&Patch| {
+                patch.patch_type == patch_type && top_level_name(patch) == name
+            };
+            let ours: Vec<Patch> = self.patches.iter().filter(&in_group).cloned().collect();
+            let theirs: Vec<Patch> = theirs.patches.iter().filter(&in_group).cloned().collect();
+
+            let overlapping = ours.iter().any(|a| theirs.iter().any(|b| overlap(a, b)));
+            if overlapping {
+                let conflict = Conflict { patch_type, name: name.to_string(), ours, theirs };
+                match resolve(&conflict) {
+                    Resolution::Ours => merged.patches.extend(conflict.ours),
+                    Resolution::Theirs => merged.patches.extend(conflict.theirs),
+                    Resolution::Neither => {}
+                    Resolution::Use(patches) => merged.patches.extend(patches),
+                }
+            } else {
+                for patch in ours.into_iter().chain(theirs) {
+                    if !merged.patches.contains(&patch) {
+                        merged.patches.push(patch);
+                    }
+                }
+            }
+        }
+
+        merged.patches.sort_by(apply_order);
+        Ok(merged)
+    }
+}
+
+/// The attribute or relationship a patch is for, still escaped for attributes
+fn top_level_name(patch: &Patch) -> &str {
+    patch.subject.split('/').next().unwrap_or("")
+}
+
+/// Whether two patches change overlapping values, where one subject is the other or contains
+/// it. Identical patches and appends to the same array do not overlap.
+fn overlap(a: &Patch, b: &Patch) -> bool {
+    if a == b {
+        return false;
+    }
+    let a_segments: Vec<&str> = a.subject.split('/').collect();
+    let b_segments: Vec<&str> = b.subject.split('/').collect();
+    if a_segments.last() == Some(&"-") && b_segments.last() == Some(&"-") {
+        return false;
+    }
+    a_segments.iter().zip(b_segments.iter()).all(|(a, b)| a == b)
+}
+
+/// Array index removals sort after other patches, from the highest index down per array, and
+/// appends sort last. Other patches keep their order.
+fn apply_order(a: &Patch, b: &Patch) -> Ordering {
+    match (removed_index(a), removed_index(b)) {
+        (Some((a_parent, a_index)), Some((b_parent, b_index))) => {
+            a_parent.cmp(b_parent).then(b_index.cmp(&a_index))
+        }
+        _ => position_class(a).cmp(&position_class(b)),
+    }
+}
+
+fn position_class(patch: &Patch) -> u8 {
+    if removed_index(patch).is_some() {
+        1
+    } else if patch.subject.ends_with("/-") {
+        2
+    } else {
+        0
+    }
+}
+
+/// The parent path and index of a patch removing an array element or to-many member
+fn removed_index(patch: &Patch) -> Option<(&str, usize)> {
+    if patch.operation != PatchOperation::Remove {
+        return None;
+    }
+    let separator = patch.subject.rfind('/')?;
+    let index = patch.subject[separator + 1..].parse().ok()?;
+    Some((&patch.subject[..separator], index))
+}
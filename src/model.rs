@@ -2,8 +2,11 @@ pub use std::collections::HashMap;
 pub use api::*;
 pub use query::{Query, QueryFields};
 use errors::*;
-use serde::{Deserialize, Serialize};
-use serde_json::{from_value, to_value, Value, Map};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{to_value, Value, Map};
+use std::cell::RefCell;
+use std::collections::HashSet;
 
 /// A trait for any struct that can be converted from/into a Resource.
 /// The only requirement is that your struct has an 'id: String' field.
@@ -17,6 +20,10 @@ pub trait JsonApiModel: Serialize
     #[doc(hidden)]
     fn jsonapi_id(&self) -> Option<String>;
     #[doc(hidden)]
+    fn jsonapi_lid(&self) -> Option<String> { None }
+    #[doc(hidden)]
+    fn lid_field() -> Option<&'static str> { None }
+    #[doc(hidden)]
     fn relationship_fields() -> Option<&'static [&'static str]>;
     #[doc(hidden)]
     fn build_relationships(&self, query: &QueryFields) -> Option<Relationships>;
@@ -39,90 +46,206 @@ pub trait JsonApiModel: Serialize
     fn from_jsonapi_resource(resource: &Resource, included: &Option<Resources>)
         -> Result<Self> 
     {
-        Self::from_serializable(Self::resource_to_attrs(resource, included))
+        Self::from_serializable(Self::resource_to_model_attrs(resource, included))
+    }
+
+    /// Convert the primary data of a document, reporting every attribute and relationship that
+    /// could not be converted rather than only the first
+    ///
+    /// Each problem is an `Error::Conversion`, see `errors::error_document`. The document is
+    /// converted again without each failing member, to find the next one.
+    fn from_jsonapi_document_checked(doc: &JsonApiDocument)
+        -> ::std::result::Result<Self, Vec<Error>>
+    {
+        let value = match doc.data {
+            Some(PrimaryData::Single(ref resource)) => {
+                to_value(Self::resource_to_model_attrs(resource, &doc.included))
+            }
+            Some(PrimaryData::Multiple(ref resources)) => {
+                let all: Vec<ResourceAttributes> = resources
+                    .iter()
+                    .map(|r| Self::resource_to_model_attrs(r, &doc.included))
+                    .collect();
+                to_value(all)
+            }
+            Some(PrimaryData::None) | None => return Err(vec![Error::MissingData]),
+        };
+        let mut value = value.map_err(|e| {
+            vec![Error::Conversion { path: String::new(), message: e.to_string() }]
+        })?;
+
+        // Leave out each failing member and try again, to find the other failing members
+        let mut errors = Vec::new();
+        let mut removed = HashSet::new();
+        loop {
+            let (path, error) = match from_value_tracked(value.clone()) {
+                Ok(model) if errors.is_empty() => return Ok(model),
+                Ok(_) => return Err(errors),
+                Err(failure) => failure,
+            };
+            if removed.contains(&path) {
+                // Missing because it was left out
+                return Err(errors);
+            }
+            errors.push(Error::Conversion {
+                path: Self::field_pointer(&path),
+                message: error.to_string(),
+            });
+            if path.is_empty() || !remove_path(&mut value, &path) {
+                return Err(errors);
+            }
+            removed.insert(path);
+        }
     }
 
     fn from_jsonapi_document(doc: &JsonApiDocument) -> Result<Self> {
         match doc.data.as_ref() {
             Some(primary_data) => {
                 match *primary_data {
-                    PrimaryData::None => bail!("Document had no data"),
+                    PrimaryData::None => Err(Error::MissingData),
                     PrimaryData::Single(ref resource) =>
                         Self::from_jsonapi_resource(resource, &doc.included),
                     PrimaryData::Multiple(ref resources) => {
                         let all: Vec<ResourceAttributes> = resources
                             .iter()
-                            .map(|r| Self::resource_to_attrs(r, &doc.included))
+                            .map(|r| Self::resource_to_model_attrs(r, &doc.included))
                             .collect();
                         Self::from_serializable(all)
                     }
                 }
             },
-            None => bail!("Document had no data")
+            None => Err(Error::MissingData)
         }
     }
 
+    /// Panics if the model can not be serialized, see `try_to_jsonapi_resource`
     fn to_jsonapi_resource(&self) -> (Resource, Option<Resources>) {
       self.to_jsonapi_resource_with_query(&Default::default())
     }
 
+    /// Panics if the model can not be serialized, see `try_to_jsonapi_resource_with_query`
     fn to_jsonapi_resource_with_query(&self, query: &Query)
       -> (Resource, Option<Resources>)
     {
-        if let Value::Object(mut attrs) = to_value(self).unwrap(){
-            let _ = attrs.remove("id");
-            let resource = Resource{
-                _type: Self::jsonapi_type().into(),
-                id: self.jsonapi_id(),
-                relationships: self.build_relationships(&query.fields),
-                attributes: self.extract_attributes(&attrs, &query.fields),
-                ..Default::default()
-            };
+        self.try_to_jsonapi_resource_with_query(query).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-            (resource, self.build_included(&query.include))
-        }else{
-            panic!(format!("{} is not a Value::Object", Self::jsonapi_type()))
-        }
+    fn try_to_jsonapi_resource(&self) -> Result<(Resource, Option<Resources>)> {
+        self.try_to_jsonapi_resource_with_query(&Default::default())
     }
 
-    
+    /// Like `to_jsonapi_resource_with_query`, but failing with `Error::Serialization` when the
+    /// model does not serialize to an object, and `Error::MissingId` when a related model has
+    /// neither an id nor a local id
+    fn try_to_jsonapi_resource_with_query(&self, query: &Query)
+        -> Result<(Resource, Option<Resources>)>
+    {
+        let serialization_error = |message: String| Error::Serialization {
+            resource_type: Self::jsonapi_type().into(),
+            message,
+        };
+        let mut attrs = match to_value(self) {
+            Ok(Value::Object(attrs)) => attrs,
+            Ok(_) => return Err(serialization_error("not serialized as an object".into())),
+            Err(e) => return Err(serialization_error(e.to_string())),
+        };
+        let _ = attrs.remove("id");
+        let resource = Resource{
+            _type: Self::jsonapi_type().into(),
+            id: self.jsonapi_id(),
+            lid: self.jsonapi_lid(),
+            relationships: self.try_build_relationships(&query.fields)?,
+            attributes: self.extract_attributes(&attrs, &query.fields),
+            ..Default::default()
+        };
+
+        Ok((resource, self.try_build_included(&query.include)?))
+    }
+
+    /// Panics if the model can not be serialized, see `try_to_jsonapi_document`
     fn to_jsonapi_document(&self) -> JsonApiDocument {
       self.to_jsonapi_document_with_query(&Default::default())
     }
 
+    /// Panics if the model can not be serialized, see `try_to_jsonapi_document_with_query`
     fn to_jsonapi_document_with_query(&self, query: &Query) -> JsonApiDocument {
-        let (resource, included) = self.to_jsonapi_resource_with_query(query);
-        JsonApiDocument {
+        self.try_to_jsonapi_document_with_query(query).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_to_jsonapi_document(&self) -> Result<JsonApiDocument> {
+        self.try_to_jsonapi_document_with_query(&Default::default())
+    }
+
+    fn try_to_jsonapi_document_with_query(&self, query: &Query) -> Result<JsonApiDocument> {
+        let (resource, included) = self.try_to_jsonapi_resource_with_query(query)?;
+        Ok(JsonApiDocument {
             data: Some(PrimaryData::Single(Box::new(resource))),
-            included: included,
+            included,
             ..Default::default()
-        }
+        })
     }
-    
+
+    /* The macro implements these for models with relationships. The defaults are for
+     * models implementing `build_relationships` and `build_included` themselves.
+     * */
+    #[doc(hidden)]
+    fn try_build_relationships(&self, query: &QueryFields) -> Result<Option<Relationships>> {
+        Ok(self.build_relationships(query))
+    }
+
+    #[doc(hidden)]
+    fn try_build_included(&self, fields: &Option<Vec<String>>) -> Result<Option<Resources>> {
+        Ok(self.build_included(fields))
+    }
+
     #[doc(hidden)]
     fn build_has_one<M: JsonApiModel>(model: &M) -> Relationship {
-        Relationship{
-            data: IdentifierData::Single(model.as_resource_identifier()),
-            links: None
-        }
+        Self::try_build_has_one(model).unwrap_or_else(|e| panic!("{}", e))
     }
-    
+
+    #[doc(hidden)]
+    fn try_build_has_one<M: JsonApiModel>(model: &M) -> Result<Relationship> {
+        Ok(Relationship{
+            data: Some(IdentifierData::Single(model.try_as_resource_identifier()?)),
+            ..Default::default()
+        })
+    }
+
     #[doc(hidden)]
     fn build_has_many<M: JsonApiModel>(models: &[M]) -> Relationship {
-        Relationship{
-            data: IdentifierData::Multiple(
-                models.iter().map(|m| m.as_resource_identifier()).collect()
-            ),
-            links: None
-        }
+        Self::try_build_has_many(models).unwrap_or_else(|e| panic!("{}", e))
     }
-    
+
+    #[doc(hidden)]
+    fn try_build_has_many<M: JsonApiModel>(models: &[M]) -> Result<Relationship> {
+        Ok(Relationship{
+            data: Some(IdentifierData::Multiple(
+                models.iter().map(|m| m.try_as_resource_identifier()).collect::<Result<_>>()?
+            )),
+            ..Default::default()
+        })
+    }
+
+    /// Panics if the model has neither an id nor a local id, see `try_as_resource_identifier`
     #[doc(hidden)]
     fn as_resource_identifier(&self) -> ResourceIdentifier {
-        ResourceIdentifier {
-            _type: Self::jsonapi_type().into(),
-            id: self.jsonapi_id().expect("Can't have ResourceIdentifier for unsafe resource"),
+        self.try_as_resource_identifier().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// The resource identifier of the model, failing with `Error::MissingId` if it has neither
+    /// an id nor a local id
+    fn try_as_resource_identifier(&self) -> Result<ResourceIdentifier> {
+        let id = self.jsonapi_id();
+        let lid = self.jsonapi_lid();
+        if id.is_none() && lid.is_none() {
+            return Err(Error::MissingId { resource_type: Self::jsonapi_type().into() });
         }
+        Ok(ResourceIdentifier {
+            _type: Self::jsonapi_type().into(),
+            id,
+            lid,
+            meta: None,
+        })
     }
 
     /* Attribute corresponding to the model is removed from the Map
@@ -134,36 +257,64 @@ pub trait JsonApiModel: Serialize
         -> ResourceAttributes 
     {
         attrs.iter().filter(|&(key, _)|{
+            if Self::lid_field() == Some(key.as_str()) {
+                return false;
+            }
             if let Some(fields) = Self::relationship_fields(){
                 if fields.contains(&key.as_str()) {
                     return false;
                 }
             }
 
-            self.should_serialize_field(query_fields, &key)
+            self.should_serialize_field(query_fields, key)
         }).map(|(k,v)|{ (k.clone(), v.clone()) }).collect()
     }
     
     #[doc(hidden)]
     fn to_resources(&self) -> Resources {
-        let (me, maybe_others) = self.to_jsonapi_resource();
+        self.try_to_resources().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[doc(hidden)]
+    fn try_to_resources(&self) -> Result<Resources> {
+        let (me, maybe_others) = self.try_to_jsonapi_resource()?;
         let mut flattened = vec![me];
         if let Some(mut others) = maybe_others {
             flattened.append(&mut others);
         }
-        flattened
+        Ok(flattened)
     }
 
     #[doc(hidden)]
     fn lookup<'a>(needle: &ResourceIdentifier, haystack: &'a [Resource])
         -> Option<&'a Resource> 
     {
-        for resource in haystack {
-            if resource._type == needle._type && resource.id.as_ref() == Some(&needle.id) {
-                return Some(resource)
-            }
+        haystack.iter().find(|resource| needle.identifies(resource))
+    }
+
+    /* Like resource_to_attrs, but also restores the model's local id
+     * field, which is carried in `lid` rather than in the attributes.
+     * */
+    #[doc(hidden)]
+    fn resource_to_model_attrs(resource: &Resource, included: &Option<Resources>)
+        -> ResourceAttributes
+    {
+        let mut attrs = Self::resource_to_attrs(resource, included);
+        if let Some(field) = Self::lid_field() {
+            attrs.insert(field.into(), to_value(resource.lid.as_ref()).unwrap_or(Value::Null));
         }
-        None
+        attrs
+    }
+
+    /* Converts a resource related by `relationship` to attributes. The macro
+     * overrides this to convert it as the related model, which also restores
+     * that model's local id field.
+     * */
+    #[doc(hidden)]
+    fn related_resource_to_attrs(_relationship: &str, resource: &Resource,
+                                 included: &Option<Resources>) -> ResourceAttributes
+    {
+        Self::resource_to_attrs(resource, included)
     }
 
     #[doc(hidden)]
@@ -177,11 +328,15 @@ pub trait JsonApiModel: Serialize
         if let Some(relations) = resource.relationships.as_ref() {
             if let Some(inc) = included.as_ref() {
                 for (name, relation) in relations {
-                    let value = match relation.data {
+                    let data = match relation.data {
+                        None => continue,
+                        Some(ref data) => data,
+                    };
+                    let value = match *data {
                         IdentifierData::None => Value::Null,
                         IdentifierData::Single(ref identifier) => {
                             let found = Self::lookup(identifier, inc)
-                                .map(|r| Self::resource_to_attrs(r, included) );
+                                .map(|r| Self::related_resource_to_attrs(name, r, included));
                             to_value(found)
                                 .expect("Casting Single relation to value")
                         },
@@ -189,7 +344,7 @@ pub trait JsonApiModel: Serialize
                             let found: Vec<Option<ResourceAttributes>> =
                                 identifiers.iter().map(|id|{
                                     Self::lookup(id, inc).map(|r|{
-                                        Self::resource_to_attrs(r, included)
+                                        Self::related_resource_to_attrs(name, r, included)
                                     })
                                 }).collect();
                             to_value(found)
@@ -206,77 +361,427 @@ pub trait JsonApiModel: Serialize
 
     #[doc(hidden)]
     fn from_serializable<S: Serialize>(s: S) -> Result<Self> {
-        from_value(to_value(s).unwrap())
-            .chain_err(|| "Error casting via serde_json")
+        let value = to_value(s).map_err(|e| Error::Conversion {
+            path: String::new(),
+            message: e.to_string(),
+        })?;
+        from_value_tracked(value).map_err(|(path, error)| {
+            Error::Conversion { path: Self::field_pointer(&path), message: error.to_string() }
+        })
+    }
+
+    /* The JSON pointer into the primary data for a path of model fields, where a leading
+     * index is the position of the resource in a collection.
+     * */
+    #[doc(hidden)]
+    fn field_pointer(path: &[String]) -> String {
+        let mut pointer = String::new();
+        let mut segments = path.iter().peekable();
+        while let Some(index) = segments.next_if(|segment| segment.parse::<usize>().is_ok()) {
+            pointer.push('/');
+            pointer.push_str(index);
+        }
+        if let Some(field) = segments.next() {
+            let is_relationship = Self::relationship_fields()
+                .is_some_and(|fields| fields.contains(&field.as_str()));
+            if field == "id" {
+                pointer.push_str("/id");
+            } else if Self::lid_field() == Some(field.as_str()) {
+                pointer.push_str("/lid");
+            } else if is_relationship {
+                pointer.push_str(&format!("/relationships/{}", pointer_segment(field)));
+            } else {
+                pointer.push_str(&format!("/attributes/{}", pointer_segment(field)));
+            }
+        }
+        pointer
     }
 }
 
+/// Panics if a model can not be serialized, see `try_vec_to_jsonapi_resources`
 pub fn vec_to_jsonapi_resources<T: JsonApiModel>(
     objects: Vec<T>,
     query: &Query,
 ) -> (Resources, Option<Resources>) {
+    try_vec_to_jsonapi_resources(objects, query).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_vec_to_jsonapi_resources<T: JsonApiModel>(
+    objects: Vec<T>,
+    query: &Query,
+) -> Result<(Resources, Option<Resources>)> {
     let mut included = vec![];
     let resources = objects
         .iter()
         .map(|obj| {
-            let (res, mut opt_incl) = obj.to_jsonapi_resource_with_query(query);
+            let (res, mut opt_incl) = obj.try_to_jsonapi_resource_with_query(query)?;
             if let Some(ref mut incl) = opt_incl {
                 included.append(incl);
             }
-            res
+            Ok(res)
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>>>()?;
     let opt_included = if included.is_empty() {
         None
     } else {
         Some(included)
     };
-    (resources, opt_included)
+    Ok((resources, opt_included))
 }
 
+/// Panics if a model can not be serialized, see `try_vec_to_jsonapi_document`
 pub fn vec_to_jsonapi_document<T: JsonApiModel>(objects: Vec<T>) -> JsonApiDocument {
   vec_to_jsonapi_document_with_query(objects, &Default::default())
 }
 
+/// Panics if a model can not be serialized, see `try_vec_to_jsonapi_document_with_query`
 pub fn vec_to_jsonapi_document_with_query<T: JsonApiModel>(
     objects: Vec<T>, query: &Query) -> JsonApiDocument
 {
-    let (resources, included) = vec_to_jsonapi_resources(objects, query);
-    JsonApiDocument {
+    try_vec_to_jsonapi_document_with_query(objects, query).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_vec_to_jsonapi_document<T: JsonApiModel>(objects: Vec<T>) -> Result<JsonApiDocument> {
+    try_vec_to_jsonapi_document_with_query(objects, &Default::default())
+}
+
+pub fn try_vec_to_jsonapi_document_with_query<T: JsonApiModel>(
+    objects: Vec<T>, query: &Query) -> Result<JsonApiDocument>
+{
+    let (resources, included) = try_vec_to_jsonapi_resources(objects, query)?;
+    Ok(JsonApiDocument {
         data: Some(PrimaryData::Multiple(resources)),
-        included: included,
+        included,
         ..Default::default()
+    })
+}
+
+/// Deserialize `value`, returning the map keys and sequence indexes leading to the member that
+/// failed along with the error. Only the members of the top-level object, or of the objects in
+/// a top-level array, are tracked.
+fn from_value_tracked<T: DeserializeOwned>(value: Value)
+    -> ::std::result::Result<T, (Vec<String>, serde_json::Error)>
+{
+    let path = RefCell::new(Vec::new());
+    T::deserialize(Tracked { value, path: &path, depth: 0 }).map_err(|error| {
+        let mut path = path.into_inner();
+        // Missing fields are only noticed at the end of their object
+        if let Some(field) = error.missing_field {
+            path.push(field.to_string());
+        }
+        (path, error.error)
+    })
+}
+
+/// The error of a `Tracked` deserializer, naming the field when it is a missing one
+#[derive(Debug)]
+struct TrackedError {
+    error: serde_json::Error,
+    missing_field: Option<&'static str>,
+}
+
+impl From<serde_json::Error> for TrackedError {
+    fn from(error: serde_json::Error) -> Self {
+        TrackedError { error, missing_field: None }
     }
 }
 
+impl ::std::fmt::Display for TrackedError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl ::std::error::Error for TrackedError {}
+
+impl de::Error for TrackedError {
+    fn custom<T: ::std::fmt::Display>(message: T) -> Self {
+        serde_json::Error::custom(message).into()
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        TrackedError { error: serde_json::Error::missing_field(field), missing_field: Some(field) }
+    }
+}
+
+/// Remove the member at a path from `from_value_tracked`
+fn remove_path(value: &mut Value, path: &[String]) -> bool {
+    match (value, path.split_first()) {
+        (&mut Value::Object(ref mut object), Some((key, []))) => object.remove(key).is_some(),
+        (&mut Value::Array(ref mut array), Some((index, rest))) => {
+            match index.parse::<usize>().ok().and_then(|index| array.get_mut(index)) {
+                Some(element) => remove_path(element, rest),
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+struct Tracked<'a> {
+    value: Value,
+    path: &'a RefCell<Vec<String>>,
+    /// Arrays are tracked at depth 0 only, objects at depth 0 and 1
+    depth: usize,
+}
+
+macro_rules! forward_to_value {
+    ($($method:ident)*) => ($(
+        fn $method<V: Visitor<'de>>(self, visitor: V)
+            -> ::std::result::Result<V::Value, TrackedError>
+        {
+            Ok(self.value.$method(visitor)?)
+        }
+    )*)
+}
+
+impl<'de, 'a> Deserializer<'de> for Tracked<'a> {
+    type Error = TrackedError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        match self.value {
+            Value::Object(object) if self.depth <= 1 => {
+                visitor.visit_map(TrackedMap {
+                    iter: object.into_iter(),
+                    value: None,
+                    path: self.path,
+                })
+            }
+            Value::Array(array) if self.depth == 0 => {
+                visitor.visit_seq(TrackedSeq {
+                    iter: array.into_iter().enumerate(),
+                    path: self.path,
+                })
+            }
+            value => Ok(value.deserialize_any(visitor)?),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str,
+                                           _fields: &'static [&'static str], visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str,
+                                         variants: &'static [&'static str], visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        Ok(self.value.deserialize_enum(name, variants, visitor)?)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        Ok(self.value.deserialize_unit_struct(name, visitor)?)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        Ok(self.value.deserialize_newtype_struct(name, visitor)?)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        Ok(self.value.deserialize_tuple(len, visitor)?)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, name: &'static str, len: usize,
+                                                 visitor: V)
+        -> ::std::result::Result<V::Value, TrackedError>
+    {
+        Ok(self.value.deserialize_tuple_struct(name, len, visitor)?)
+    }
+
+    forward_to_value! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_bytes
+        deserialize_byte_buf deserialize_unit deserialize_identifier deserialize_ignored_any
+    }
+}
+
+struct TrackedMap<'a> {
+    iter: ::serde_json::map::IntoIter,
+    value: Option<(String, Value)>,
+    path: &'a RefCell<Vec<String>>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for TrackedMap<'a> {
+    type Error = TrackedError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K)
+        -> ::std::result::Result<Option<K::Value>, TrackedError>
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some((key.clone(), value));
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S)
+        -> ::std::result::Result<S::Value, TrackedError>
+    {
+        let (key, value) = match self.value.take() {
+            Some(entry) => entry,
+            None => return Err(de::Error::custom("value missing")),
+        };
+        self.path.borrow_mut().push(key);
+        let result = seed.deserialize(value)?;
+        self.path.borrow_mut().pop();
+        Ok(result)
+    }
+}
+
+struct TrackedSeq<'a> {
+    iter: ::std::iter::Enumerate<::std::vec::IntoIter<Value>>,
+    path: &'a RefCell<Vec<String>>,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for TrackedSeq<'a> {
+    type Error = TrackedError;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S)
+        -> ::std::result::Result<Option<S::Value>, TrackedError>
+    {
+        let (index, value) = match self.iter.next() {
+            Some(element) => element,
+            None => return Ok(None),
+        };
+        self.path.borrow_mut().push(index.to_string());
+        let result = seed.deserialize(Tracked { value, path: self.path, depth: 1 })?;
+        self.path.borrow_mut().pop();
+        Ok(Some(result))
+    }
+}
+
+/* Used by `jsonapi_model!` to convert a related resource as the model type of
+ * a `has one` field, which the closure only names
+ * */
+#[doc(hidden)]
+pub fn related_model_attrs<M, T: JsonApiModel>(_field: fn(&M) -> &T, resource: &Resource,
+                                               included: &Option<Resources>)
+    -> ResourceAttributes
+{
+    T::resource_to_model_attrs(resource, included)
+}
+
+/* Like related_model_attrs, for the element type of a `has many` field */
+#[doc(hidden)]
+pub fn related_models_attrs<M, T: JsonApiModel>(_field: fn(&M) -> &Vec<T>, resource: &Resource,
+                                                included: &Option<Resources>)
+    -> ResourceAttributes
+{
+    T::resource_to_model_attrs(resource, included)
+}
+
+/// Implements `JsonApiModel` for a struct with an `id` field.
+///
+/// An optional `lid <field>` clause names a field holding the local id (`lid`).
+///
+/// ```
+/// # #[macro_use] extern crate jsonapi;
+/// # #[macro_use] extern crate serde_derive;
+/// use jsonapi::model::*;
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Flea {
+///     id: Option<String>,
+///     local_id: Option<String>,
+///     name: String,
+/// }
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Dog {
+///     id: Option<String>,
+///     name: String,
+///     main_flea: Flea,
+///     fleas: Vec<Flea>,
+/// }
+///
+/// jsonapi_model!(Flea; "flea"; lid local_id);
+/// jsonapi_model!(Dog; "dog"; has one main_flea; has many fleas);
+///
+/// # fn main() {
+/// let flea = |local_id: &str| {
+///     Flea { id: None, local_id: Some(local_id.into()), name: local_id.into() }
+/// };
+/// let dog = Dog {
+///     id: Some("1".into()),
+///     name: "fido".into(),
+///     main_flea: flea("itchy"),
+///     fleas: vec![flea("itchy"), flea("scratchy")],
+/// };
+///
+/// let doc = dog.to_jsonapi_document();
+/// let dog = Dog::from_jsonapi_document(&doc).unwrap();
+/// assert_eq!(dog.main_flea.local_id, Some("itchy".into()));
+/// assert_eq!(dog.fleas[1].local_id, Some("scratchy".into()));
+/// # }
+/// ```
 #[macro_export]
 macro_rules! jsonapi_model {
-    ($model:ty; $type:expr) => (
+    (@lid $lid:ident) => (
+        fn jsonapi_lid(&self) -> Option<String> { self.$lid.clone().map(|s| s.to_string()) }
+        fn lid_field() -> Option<&'static str> { Some(stringify!($lid)) }
+    );
+    ($model:ty; $type:expr $(; lid $lid:ident)*) => (
         impl JsonApiModel for $model {
             fn jsonapi_type() -> &'static str { $type }
             fn jsonapi_id(&self) -> Option<String> { self.id.clone().map(|s| s.to_string()) }
+            $( jsonapi_model!(@lid $lid); )*
             fn relationship_fields() -> Option<&'static [&'static str]> { None }
             fn build_relationships(&self, _query: &QueryFields) -> Option<Relationships> { None }
             fn build_included(&self, _fields: &Option<Vec<String>>) -> Option<Resources> { None }
         }
     );
-    ($model:ty; $type:expr;
+    ($model:ty; $type:expr; $(lid $lid:ident;)*
         has one $( $has_one:ident ),*
     ) => (
-        jsonapi_model!($model; $type; has one $( $has_one ),*; has many);
+        jsonapi_model!($model; $type; $(lid $lid;)* has one $( $has_one ),*; has many);
     );
-    ($model:ty; $type:expr;
+    ($model:ty; $type:expr; $(lid $lid:ident;)*
         has many $( $has_many:ident ),*
     ) => (
-        jsonapi_model!($model; $type; has one; has many $( $has_many ),*);
+        jsonapi_model!($model; $type; $(lid $lid;)* has one; has many $( $has_many ),*);
     );
-    ($model:ty; $type:expr;
+    ($model:ty; $type:expr; $(lid $lid:ident;)*
         has one $( $has_one:ident ),*;
         has many $( $has_many:ident ),*
     ) => (
         impl JsonApiModel for $model {
             fn jsonapi_type() -> &'static str { $type }
             fn jsonapi_id(&self) -> Option<String> { self.id.clone().map(|s| s.to_string()) }
+            $( jsonapi_model!(@lid $lid); )*
 
             fn relationship_fields() -> Option<&'static [&'static str]> {
                 static FIELDS: &'static [&'static str] = &[
@@ -286,50 +791,80 @@ macro_rules! jsonapi_model {
 
                 Some(FIELDS)
             }
+
+            fn related_resource_to_attrs(relationship: &str, resource: &Resource,
+                                         included: &Option<Resources>) -> ResourceAttributes
+            {
+                $(
+                    if relationship == stringify!($has_one) {
+                        return $crate::model::related_model_attrs(
+                            |model: &Self| &model.$has_one, resource, included);
+                    }
+                )*
+                $(
+                    if relationship == stringify!($has_many) {
+                        return $crate::model::related_models_attrs(
+                            |model: &Self| &model.$has_many, resource, included);
+                    }
+                )*
+                Self::resource_to_attrs(resource, included)
+            }
             
             fn build_relationships(&self, fields: &QueryFields)
               -> Option<Relationships>
+            {
+                self.try_build_relationships(fields).unwrap_or_else(|e| panic!("{}", e))
+            }
+
+            fn try_build_relationships(&self, fields: &QueryFields)
+              -> $crate::errors::Result<Option<Relationships>>
             {
                 let mut relationships = HashMap::new();
                 $(
                     if self.should_serialize_field(fields, stringify!($has_one)) {
                       relationships.insert(stringify!($has_one).into(),
-                          Self::build_has_one(&self.$has_one)
+                          Self::try_build_has_one(&self.$has_one)?
                       );
                     }
                 )*
                 $(
                     if self.should_serialize_field(fields, stringify!($has_many)) {
                         relationships.insert(stringify!($has_many).into(),
-                            Self::build_has_many(&self.$has_many)
+                            Self::try_build_has_many(&self.$has_many)?
                         );
                     }
                 )*
                 if relationships.is_empty() {
-                  None
+                  Ok(None)
                 } else {
-                  Some(relationships)
+                  Ok(Some(relationships))
                 }
             }
-            
+
             fn build_included(&self, fields: &Option<Vec<String>>) -> Option<Resources> {
+                self.try_build_included(fields).unwrap_or_else(|e| panic!("{}", e))
+            }
+
+            fn try_build_included(&self, fields: &Option<Vec<String>>)
+              -> $crate::errors::Result<Option<Resources>>
+            {
                 let mut included:Resources = vec![];
                 $( 
                     if self.should_include(fields, stringify!($has_one)) {
-                        included.append(&mut self.$has_one.to_resources());
+                        included.append(&mut self.$has_one.try_to_resources()?);
                     }
                 )*
                 $(
                     if self.should_include(fields, stringify!($has_many)) {
                         for model in &self.$has_many {
-                            included.append(&mut model.to_resources());
+                            included.append(&mut model.try_to_resources()?);
                         }
                     }
                 )*
                 if included.is_empty() {
-                  None
+                  Ok(None)
                 } else {
-                  Some(included)
+                  Ok(Some(included))
                 }
             }
         }
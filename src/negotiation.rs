@@ -0,0 +1,315 @@
+//! Content negotiation for the JSON-API media type, `application/vnd.api+json`
+//!
+//! A `Negotiator` checks `Content-Type` and `Accept` headers against the supported `ext` and
+//! `profile` URIs.
+//!
+//! ```
+//! use jsonapi::negotiation::{Negotiator, NegotiationError};
+//!
+//! let negotiator = Negotiator::new()
+//!     .with_extension("https://jsonapi.org/ext/atomic");
+//!
+//! let media_type = negotiator
+//!     .accept(Some(r#"application/vnd.api+json; ext="https://jsonapi.org/ext/atomic""#))
+//!     .unwrap();
+//! assert_eq!(media_type.ext, vec!["https://jsonapi.org/ext/atomic".to_string()]);
+//!
+//! assert_eq!(negotiator.content_type(Some("application/vnd.api+json; charset=utf-8")),
+//!            Err(NegotiationError::UnsupportedMediaType));
+//! ```
+
+use api::{JsonApiError, JsonApiInfo};
+use std::fmt;
+
+/// The JSON-API media type
+pub const MEDIA_TYPE: &str = "application/vnd.api+json";
+
+/// An instance of the JSON-API media type, with its `ext` and `profile` parameters
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MediaType {
+    pub ext: Vec<String>,
+    pub profile: Vec<String>,
+}
+
+/// Negotiation failures, each corresponding to an HTTP status code
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub enum NegotiationError {
+    /// `415 Unsupported Media Type`, for a `Content-Type` the server can not process
+    UnsupportedMediaType,
+    /// `406 Not Acceptable`, when no JSON-API media type in `Accept` can be satisfied
+    NotAcceptable,
+}
+
+/// Negotiates the JSON-API media type against the supported extensions and profiles
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Negotiator {
+    pub extensions: Vec<String>,
+    pub profiles: Vec<String>,
+}
+
+/// A single media range from a header value
+enum MediaRange {
+    /// The JSON-API media type, with only `ext` and `profile` parameters
+    JsonApi(MediaType, f32),
+    /// The JSON-API media type, with some other media type parameter
+    JsonApiWithParameters,
+    /// Any other media type
+    Other,
+}
+
+impl MediaType {
+    /// Parse a single media type, such as a `Content-Type` header value.
+    ///
+    /// Returns `None` for other media types, and for the JSON-API media type with media type
+    /// parameters other than `ext` and `profile`.
+    ///
+    /// ```
+    /// use jsonapi::negotiation::MediaType;
+    ///
+    /// let media_type = MediaType::parse(r#"application/vnd.api+json;profile="http://a http://b""#)
+    ///     .unwrap();
+    /// assert_eq!(media_type.profile.len(), 2);
+    /// assert!(MediaType::parse("application/json").is_none());
+    /// ```
+    pub fn parse(s: &str) -> Option<MediaType> {
+        match parse_media_range(s, false) {
+            MediaRange::JsonApi(media_type, _) => Some(media_type),
+            _ => None,
+        }
+    }
+
+    /// The `JsonApiInfo` object advertising this media type's extensions and profiles
+    pub fn to_jsonapi_info(&self) -> JsonApiInfo {
+        JsonApiInfo {
+            version: Some("1.1".into()),
+            ext: if self.ext.is_empty() { None } else { Some(self.ext.clone()) },
+            profile: if self.profile.is_empty() { None } else { Some(self.profile.clone()) },
+            meta: None,
+        }
+    }
+}
+
+/// Formats the media type for use as a response `Content-Type`
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", MEDIA_TYPE)?;
+        if !self.ext.is_empty() {
+            write!(f, "; ext=\"{}\"", self.ext.join(" "))?;
+        }
+        if !self.profile.is_empty() {
+            write!(f, "; profile=\"{}\"", self.profile.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Negotiator {
+    pub fn new() -> Self {
+        Negotiator::default()
+    }
+
+    /// Add a supported extension URI
+    pub fn with_extension(mut self, uri: &str) -> Self {
+        self.extensions.push(uri.into());
+        self
+    }
+
+    /// Add a supported profile URI
+    pub fn with_profile(mut self, uri: &str) -> Self {
+        self.profiles.push(uri.into());
+        self
+    }
+
+    /// Check a request `Content-Type` header value.
+    ///
+    /// A missing header is accepted. Unsupported profiles are ignored.
+    pub fn content_type(&self, header: Option<&str>)
+        -> Result<MediaType, NegotiationError>
+    {
+        let header = match header {
+            None => return Ok(MediaType::default()),
+            Some(header) => header,
+        };
+
+        match parse_media_range(header, false) {
+            MediaRange::JsonApi(media_type, _) => {
+                if self.supports_extensions(&media_type) {
+                    Ok(self.applicable(media_type))
+                } else {
+                    Err(NegotiationError::UnsupportedMediaType)
+                }
+            }
+            _ => Err(NegotiationError::UnsupportedMediaType),
+        }
+    }
+
+    /// Choose the media type for a response from a request `Accept` header value.
+    ///
+    /// Picks the acceptable JSON-API media type with the highest quality, or the plain media type
+    /// when `Accept` does not mention it.
+    pub fn accept(&self, header: Option<&str>) -> Result<MediaType, NegotiationError> {
+        let header = match header {
+            None => return Ok(MediaType::default()),
+            Some(header) => header,
+        };
+
+        let mut found_jsonapi = false;
+        let mut best: Option<(MediaType, f32)> = None;
+
+        for range in split_unquoted(header, ',') {
+            match parse_media_range(&range, true) {
+                MediaRange::Other => {}
+                MediaRange::JsonApiWithParameters => found_jsonapi = true,
+                MediaRange::JsonApi(media_type, quality) => {
+                    found_jsonapi = true;
+                    if quality <= 0.0 || !self.supports_extensions(&media_type) {
+                        continue;
+                    }
+                    let better = match best {
+                        None => true,
+                        Some((_, best_quality)) => quality > best_quality,
+                    };
+                    if better {
+                        best = Some((media_type, quality));
+                    }
+                }
+            }
+        }
+
+        match best {
+            Some((media_type, _)) => Ok(self.applicable(media_type)),
+            None if found_jsonapi => Err(NegotiationError::NotAcceptable),
+            None => Ok(MediaType::default()),
+        }
+    }
+
+    fn supports_extensions(&self, media_type: &MediaType) -> bool {
+        media_type.ext.iter().all(|ext| self.extensions.contains(ext))
+    }
+
+    /// Drop the profiles this server does not support
+    fn applicable(&self, media_type: MediaType) -> MediaType {
+        MediaType {
+            ext: media_type.ext,
+            profile: media_type.profile
+                .into_iter()
+                .filter(|profile| self.profiles.contains(profile))
+                .collect(),
+        }
+    }
+}
+
+impl NegotiationError {
+    /// The HTTP status code for this error
+    pub fn status(&self) -> u16 {
+        match *self {
+            NegotiationError::UnsupportedMediaType => 415,
+            NegotiationError::NotAcceptable => 406,
+        }
+    }
+
+    /// A `JsonApiError` describing this error, for use in an error document
+    pub fn to_jsonapi_error(&self) -> JsonApiError {
+        let title = match *self {
+            NegotiationError::UnsupportedMediaType => "Unsupported Media Type",
+            NegotiationError::NotAcceptable => "Not Acceptable",
+        };
+        JsonApiError {
+            status: Some(self.status().to_string()),
+            title: Some(title.into()),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for NegotiationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NegotiationError::UnsupportedMediaType => write!(f, "415 Unsupported Media Type"),
+            NegotiationError::NotAcceptable => write!(f, "406 Not Acceptable"),
+        }
+    }
+}
+
+/// Parse one media range. In an `Accept` header the `q` parameter and any accept-extensions
+/// following it are not media type parameters.
+fn parse_media_range(range: &str, accept: bool) -> MediaRange {
+    let mut parts = split_unquoted(range, ';').into_iter();
+    let essence = parts.next().unwrap_or_default();
+    if !essence.trim().eq_ignore_ascii_case(MEDIA_TYPE) {
+        return MediaRange::Other;
+    }
+
+    let mut media_type = MediaType::default();
+    let mut quality = 1.0;
+    let mut has_other_parameters = false;
+
+    for parameter in parts {
+        let (name, value) = match parameter.find('=') {
+            Some(index) => (parameter[..index].trim().to_lowercase(),
+                            unquote(parameter[index + 1..].trim())),
+            None => (parameter.trim().to_lowercase(), String::new()),
+        };
+        match name.as_str() {
+            "ext" => media_type.ext = value.split_whitespace().map(|s| s.to_string()).collect(),
+            "profile" => {
+                media_type.profile = value.split_whitespace().map(|s| s.to_string()).collect()
+            }
+            "q" if accept => {
+                quality = value.parse().unwrap_or(0.0);
+                break;
+            }
+            _ => has_other_parameters = true,
+        }
+    }
+
+    if has_other_parameters {
+        MediaRange::JsonApiWithParameters
+    } else {
+        MediaRange::JsonApi(media_type, quality)
+    }
+}
+
+/// Split on `separator` outside of quoted strings
+fn split_unquoted(s: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in s.chars() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(current.clone());
+            current.clear();
+            continue;
+        }
+        current.push(c);
+    }
+    parts.push(current);
+
+    parts.into_iter().filter(|part| !part.trim().is_empty()).collect()
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut unquoted = String::new();
+        let mut escaped = false;
+        for c in value[1..value.len() - 1].chars() {
+            if !escaped && c == '\\' {
+                escaped = true;
+            } else {
+                escaped = false;
+                unquoted.push(c);
+            }
+        }
+        unquoted
+    } else {
+        value.to_string()
+    }
+}
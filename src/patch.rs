@@ -0,0 +1,745 @@
+//! Request documents and RFC 6902 JSON Patch for the changes in a `PatchSet`
+//!
+//! Covers `PATCH` request documents, relationship endpoint updates, RFC 6902 JSON Patch and
+//! RFC 7386 JSON Merge Patch.
+//!
+//! ```
+//! use jsonapi::api::*;
+//! use std::str::FromStr;
+//!
+//! let before = Resource::from_str(r#"{
+//!   "type": "articles", "id": "1",
+//!   "attributes": { "title": "Rails is Omakase", "likes": 250 },
+//!   "relationships": { "tags": { "data": [{ "type": "tags", "id": "1" }] } }
+//! }"#).unwrap();
+//! let mut after = before.clone();
+//! after.attributes.insert("title".into(), "Rails was Omakase".into());
+//! after.relationships = Resource::from_str(r#"{
+//!   "type": "articles", "id": "1",
+//!   "relationships": { "tags": { "data": [{ "type": "tags", "id": "2" }] } }
+//! }"#).unwrap().relationships;
+//!
+//! let patchset = before.diff(after).unwrap();
+//!
+//! let doc = patchset.to_patch_document().unwrap();
+//! match doc.data {
+//!     Some(PrimaryData::Single(ref resource)) => {
+//!         assert_eq!(resource.attributes.len(), 1);
+//!         assert!(resource.relationships.is_none());
+//!     }
+//!     _ => panic!("expected a single resource"),
+//! }
+//!
+//! let updates = patchset.to_relationship_updates();
+//! assert_eq!(updates[0].method(), "DELETE");
+//! assert_eq!(updates[1].method(), "POST");
+//! assert_eq!(updates[1].path(), "/articles/1/relationships/tags");
+//! ```
+
+use api::*;
+use errors::{self, Error};
+use serde_json;
+use serde_json::map::Entry;
+
+/// A request to a to-many relationship endpoint, adding or removing members
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelationshipUpdate {
+    pub resource_type: String,
+    pub resource_id: String,
+    pub relationship: String,
+    /// `PatchOperation::Add` to add the members, `PatchOperation::Remove` to remove them
+    pub operation: PatchOperation,
+    /// The request document, with the resource identifiers of the members as `data`
+    pub document: Relationship,
+}
+
+impl RelationshipUpdate {
+    /// The HTTP method for this request
+    pub fn method(&self) -> &'static str {
+        match self.operation {
+            PatchOperation::Remove => "DELETE",
+            PatchOperation::Add | PatchOperation::Replace => "POST",
+        }
+    }
+
+    /// The path of the relationship endpoint, `/<type>/<id>/relationships/<name>`
+    pub fn path(&self) -> String {
+        format!("/{}/{}/relationships/{}", self.resource_type, self.resource_id,
+                self.relationship)
+    }
+}
+
+impl PatchSet {
+    /// The body of a `PATCH` request for the resource, with only the changed attributes and
+    /// relationships
+    ///
+    /// Members added to or removed from a to-many relationship are left out, see
+    /// `to_relationship_updates`. Fails with `Error::InvalidPatch` for nested attribute patches.
+    pub fn to_patch_document(&self) -> errors::Result<JsonApiDocument> {
+        let mut resource = Resource {
+            _type: self.resource_type.clone(),
+            id: Some(self.resource_id.clone()),
+            ..Default::default()
+        };
+        let mut relationships = Relationships::new();
+
+        for patch in &self.patches {
+            match patch.patch_type {
+                PatchType::Attribute if !patch.subject.contains('/') => {
+                    let name = unescape_pointer_segment(&patch.subject);
+                    resource.attributes.insert(name, patch.next.clone());
+                }
+                PatchType::Attribute => {
+                    return Err(Error::InvalidPatch {
+                        subject: patch.subject.clone(),
+                        message: "a PATCH document needs the whole attribute value".into(),
+                    });
+                }
+                PatchType::Relationship if !patch.subject.contains('/') => {
+                    let data = match patch.operation {
+                        PatchOperation::Remove if patch.previous.is_array() => {
+                            IdentifierData::Multiple(ResourceIdentifiers::new())
+                        }
+                        PatchOperation::Remove => IdentifierData::None,
+                        _ => serde_json::from_value(patch.next.clone()).map_err(|e| {
+                            Error::InvalidPatch {
+                                subject: patch.subject.clone(),
+                                message: format!("invalid relationship data: {}", e),
+                            }
+                        })?,
+                    };
+                    relationships.insert(patch.subject.clone(), Relationship {
+                        data: Some(data),
+                        ..Default::default()
+                    });
+                }
+                PatchType::Relationship => {}
+            }
+        }
+
+        if !relationships.is_empty() {
+            resource.relationships = Some(relationships);
+        }
+
+        Ok(JsonApiDocument {
+            data: Some(PrimaryData::Single(Box::new(resource))),
+            ..Default::default()
+        })
+    }
+
+    /// The requests to to-many relationship endpoints for the members added to or removed from
+    /// a relationship, ordered by relationship name with removals first
+    pub fn to_relationship_updates(&self) -> Vec<RelationshipUpdate> {
+        let mut updates: Vec<RelationshipUpdate> = Vec::new();
+
+        for patch in &self.patches {
+            if patch.patch_type != PatchType::Relationship {
+                continue;
+            }
+            let name = match patch.subject.find('/') {
+                Some(index) => &patch.subject[..index],
+                None => continue,
+            };
+            let value = match patch.operation {
+                PatchOperation::Remove => &patch.previous,
+                _ => &patch.next,
+            };
+            let identifier: ResourceIdentifier = match serde_json::from_value(value.clone()) {
+                Ok(identifier) => identifier,
+                Err(_) => continue,
+            };
+
+            let position = updates
+                .iter()
+                .position(|u| u.relationship == name && u.operation == patch.operation);
+            let update = match position {
+                Some(position) => &mut updates[position],
+                None => {
+                    updates.push(RelationshipUpdate {
+                        resource_type: self.resource_type.clone(),
+                        resource_id: self.resource_id.clone(),
+                        relationship: name.to_string(),
+                        operation: patch.operation,
+                        document: Relationship {
+                            data: Some(IdentifierData::Multiple(ResourceIdentifiers::new())),
+                            ..Default::default()
+                        },
+                    });
+                    updates.last_mut().unwrap()
+                }
+            };
+            if let Some(IdentifierData::Multiple(ref mut identifiers)) = update.document.data {
+                identifiers.push(identifier);
+            }
+        }
+
+        for update in &mut updates {
+            if update.operation == PatchOperation::Remove {
+                // `Resource::diff` lists removals from the highest index down
+                if let Some(IdentifierData::Multiple(ref mut identifiers)) = update.document.data {
+                    identifiers.reverse();
+                }
+            }
+        }
+        updates.sort_by(|a, b| {
+            (&a.relationship, a.operation != PatchOperation::Remove)
+                .cmp(&(&b.relationship, b.operation != PatchOperation::Remove))
+        });
+        updates
+    }
+}
+
+/// An RFC 6902 JSON Patch operation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum JsonPatchOperation {
+    Add { path: String, value: JsonApiValue },
+    Remove { path: String },
+    Replace { path: String, value: JsonApiValue },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: JsonApiValue },
+}
+
+impl PatchSet {
+    /// Convert to JSON Patch operations on the resource object
+    ///
+    /// Every `remove` and `replace` is preceded by a `test` of the previous value. Removals of
+    /// the last element (`<name>/-`, as from `invert`) fail with `JsonPatchError::Unsupported`,
+    /// see `to_json_patch_for`.
+    ///
+    /// ```
+    /// use jsonapi::api::*;
+    /// use jsonapi::patch::JsonPatchOperation;
+    /// use std::str::FromStr;
+    ///
+    /// let before = Resource::from_str(r#"{
+    ///   "type": "articles", "id": "1", "attributes": { "title": "Rails is Omakase" }
+    /// }"#).unwrap();
+    /// let mut after = before.clone();
+    /// after.attributes.insert("title".into(), "Rails was Omakase".into());
+    ///
+    /// let patchset = before.diff(after.clone()).unwrap();
+    /// let json_patch = patchset.to_json_patch().unwrap();
+    /// assert_eq!(json_patch[1], JsonPatchOperation::Replace {
+    ///     path: "/attributes/title".into(),
+    ///     value: "Rails was Omakase".into(),
+    /// });
+    ///
+    /// assert_eq!(before.apply_json_patch(&json_patch).unwrap(), after);
+    /// assert_eq!(PatchSet::from_json_patch("articles", "1", &json_patch).unwrap(), patchset);
+    /// ```
+    pub fn to_json_patch(&self) -> Result<Vec<JsonPatchOperation>, JsonPatchError> {
+        let mut operations = Vec::new();
+        for patch in &self.patches {
+            push_json_operations(patch, &mut operations)?;
+        }
+        Ok(operations)
+    }
+
+    /// Like `to_json_patch`, resolving removals of the last element against `resource`, the
+    /// resource the patches apply to
+    pub fn to_json_patch_for(&self, resource: &Resource)
+        -> Result<Vec<JsonPatchOperation>, JsonPatchError>
+    {
+        let mut current = resource.clone();
+        let mut operations = Vec::new();
+        for patch in &self.patches {
+            let patch = current.resolve_last_element(patch);
+            push_json_operations(&patch, &mut operations)?;
+            current.apply_patch(&patch)
+                .map_err(|_| JsonPatchError::PathNotFound(patch.subject.clone()))?;
+        }
+        Ok(operations)
+    }
+
+    /// Convert JSON Patch operations on a resource object, as produced by `to_json_patch`, to a
+    /// `PatchSet`
+    ///
+    /// `previous` is taken from a preceding `test` of the same path. `move` and `copy` are
+    /// `Unsupported`.
+    pub fn from_json_patch(resource_type: &str, resource_id: &str,
+                           operations: &[JsonPatchOperation])
+        -> Result<PatchSet, JsonPatchError>
+    {
+        let mut patchset = PatchSet {
+            resource_type: resource_type.into(),
+            resource_id: resource_id.into(),
+            patches: Vec::new(),
+        };
+        let mut tested: Option<(&str, &JsonApiValue)> = None;
+
+        for operation in operations {
+            let (operation, path, next) = match *operation {
+                JsonPatchOperation::Test { ref path, ref value } => {
+                    tested = Some((path, value));
+                    continue;
+                }
+                JsonPatchOperation::Add { ref path, ref value } => {
+                    (PatchOperation::Add, path, value.clone())
+                }
+                JsonPatchOperation::Remove { ref path } => {
+                    (PatchOperation::Remove, path, JsonApiValue::Null)
+                }
+                JsonPatchOperation::Replace { ref path, ref value } => {
+                    (PatchOperation::Replace, path, value.clone())
+                }
+                JsonPatchOperation::Move { ref path, .. } |
+                JsonPatchOperation::Copy { ref path, .. } => {
+                    return Err(JsonPatchError::Unsupported(path.clone()))
+                }
+            };
+
+            let tokens = parse_pointer(path)?;
+            let tokens: Vec<&str> = tokens.iter().map(|token| token.as_str()).collect();
+            let (patch_type, operation, subject, test_path, next) = match tokens[..] {
+                ["attributes", _, ..] => {
+                    let subject = path["/attributes/".len()..].to_string();
+                    (PatchType::Attribute, operation, subject, path.clone(), next)
+                }
+                ["relationships", name] => {
+                    let next = next.get("data").cloned().unwrap_or(JsonApiValue::Null);
+                    let test_path = format!("{}/data", path);
+                    (PatchType::Relationship, operation, name.to_string(), test_path, next)
+                }
+                // Adding relationship data that must already exist replaces it
+                ["relationships", name, "data"] if operation != PatchOperation::Remove => {
+                    let operation = PatchOperation::Replace;
+                    (PatchType::Relationship, operation, name.to_string(), path.clone(), next)
+                }
+                ["relationships", name, "data", member] => {
+                    let subject = format!("{}/{}", name, member);
+                    (PatchType::Relationship, operation, subject, path.clone(), next)
+                }
+                _ => return Err(JsonPatchError::Unsupported(path.clone())),
+            };
+
+            let previous = previous_value(tested.take(), &test_path);
+            patchset.push(Patch { patch_type, operation, subject, previous, next });
+        }
+
+        Ok(patchset)
+    }
+}
+
+/// Append the JSON Patch operations for `patch`
+fn push_json_operations(patch: &Patch, operations: &mut Vec<JsonPatchOperation>)
+    -> Result<(), JsonPatchError>
+{
+    let (test_path, path, value) = match patch.patch_type {
+        PatchType::Attribute => {
+            let path = format!("/attributes/{}", patch.subject);
+            (path.clone(), path, patch.next.clone())
+        }
+        PatchType::Relationship => {
+            let mut segments = patch.subject.splitn(2, '/');
+            let name = format!("/relationships/{}",
+                               pointer_segment(segments.next().unwrap_or("")));
+            match segments.next() {
+                Some(member) => {
+                    let path = format!("{}/data/{}", name, member);
+                    (path.clone(), path, patch.next.clone())
+                }
+                None if patch.operation == PatchOperation::Replace => {
+                    let path = format!("{}/data", name);
+                    (path.clone(), path, patch.next.clone())
+                }
+                None => {
+                    let test_path = format!("{}/data", name);
+                    let mut relationship = serde_json::Map::new();
+                    relationship.insert("data".into(), patch.next.clone());
+                    (test_path, name, JsonApiValue::Object(relationship))
+                }
+            }
+        }
+    };
+
+    match patch.operation {
+        PatchOperation::Add => operations.push(JsonPatchOperation::Add { path, value }),
+        PatchOperation::Remove if path.ends_with("/-") => {
+            return Err(JsonPatchError::Unsupported(path))
+        }
+        PatchOperation::Remove => {
+            operations.push(JsonPatchOperation::Test {
+                path: test_path,
+                value: patch.previous.clone(),
+            });
+            operations.push(JsonPatchOperation::Remove { path });
+        }
+        PatchOperation::Replace => {
+            operations.push(JsonPatchOperation::Test {
+                path: test_path,
+                value: patch.previous.clone(),
+            });
+            operations.push(JsonPatchOperation::Replace { path, value });
+        }
+    }
+    Ok(())
+}
+
+/// The `JsonPatchError` for a `DiffPatchError` of the patched resource
+fn diff_error(error: DiffPatchError) -> JsonPatchError {
+    match error {
+        DiffPatchError::IncompatibleTypes(..) => JsonPatchError::Unsupported("/type".into()),
+        DiffPatchError::IncompatibleIds(..) => JsonPatchError::Unsupported("/id".into()),
+        DiffPatchError::MissingId => JsonPatchError::InvalidResource("resource has no id".into()),
+        DiffPatchError::NonExistentProperty(subject) => JsonPatchError::PathNotFound(subject),
+        DiffPatchError::IncorrectPropertyValue(subject) => JsonPatchError::TestFailed(subject),
+        DiffPatchError::UnsupportedChange(name) => {
+            JsonPatchError::Unsupported(format!("/relationships/{}", pointer_segment(&name)))
+        }
+    }
+}
+
+fn previous_value(tested: Option<(&str, &JsonApiValue)>, path: &str) -> JsonApiValue {
+    match tested {
+        Some((tested_path, value)) if tested_path == path => value.clone(),
+        _ => JsonApiValue::Null,
+    }
+}
+
+impl Resource {
+    /// Apply RFC 6902 JSON Patch operations to this resource object, returning the patched
+    /// resource. Nothing is applied when an operation fails.
+    pub fn apply_json_patch(&self, operations: &[JsonPatchOperation])
+        -> Result<Resource, JsonPatchError>
+    {
+        let mut value = serde_json::to_value(self)
+            .map_err(|e| JsonPatchError::InvalidResource(e.to_string()))?;
+        if let JsonApiValue::Object(ref mut members) = value {
+            members.entry("relationships")
+                .or_insert_with(|| JsonApiValue::Object(serde_json::Map::new()));
+        }
+        for operation in operations {
+            apply_operation(&mut value, operation)?;
+        }
+        let was_empty = self.relationships.as_ref().is_some_and(|r| r.is_empty());
+        if let JsonApiValue::Object(ref mut members) = value {
+            let emptied = members.get("relationships")
+                .and_then(JsonApiValue::as_object)
+                .is_some_and(|relationships| relationships.is_empty());
+            if emptied && !was_empty {
+                members.remove("relationships");
+            }
+        }
+        serde_json::from_value(value).map_err(|e| JsonPatchError::InvalidResource(e.to_string()))
+    }
+}
+
+/// Resource object members a merge patch may change
+const MERGE_PATCH_MEMBERS: [&str; 2] = ["attributes", "relationships"];
+
+impl Resource {
+    /// Apply an RFC 7386 JSON Merge Patch to the `attributes` and relationship `data` of this
+    /// resource object, returning the patched resource and the `PatchSet` from `diff_deep`
+    ///
+    /// Changes a `PatchSet` can not express, to `meta` or relationship `links`, fail with
+    /// `JsonPatchError::Unsupported`, as do `null` relationship data and removals of
+    /// relationships without data.
+    ///
+    /// ```
+    /// # #[macro_use] extern crate serde_json;
+    /// # extern crate jsonapi;
+    /// use jsonapi::api::*;
+    /// use std::str::FromStr;
+    ///
+    /// # fn main() {
+    /// let article = Resource::from_str(r#"{
+    ///   "type": "articles", "id": "1",
+    ///   "attributes": { "title": "Rails is Omakase", "settings": { "theme": "dark" } }
+    /// }"#).unwrap();
+    ///
+    /// let (patched, patchset) = article.apply_merge_patch(&json!({
+    ///     "attributes": { "title": null, "settings": { "font": "serif" } }
+    /// })).unwrap();
+    /// assert!(patched.get_attribute("title").is_none());
+    /// assert_eq!(patched.get_attribute("settings").unwrap(),
+    ///            &json!({ "theme": "dark", "font": "serif" }));
+    /// assert_eq!(patchset.patches.len(), 2);
+    /// assert_eq!(article.diff_merge_patch(&patched).unwrap(), json!({
+    ///     "attributes": { "title": null, "settings": { "font": "serif" } }
+    /// }));
+    /// # }
+    /// ```
+    pub fn apply_merge_patch(&self, merge_patch: &JsonApiValue)
+        -> Result<(Resource, PatchSet), JsonPatchError>
+    {
+        let members = match *merge_patch {
+            JsonApiValue::Object(ref members) => members,
+            _ => return Err(JsonPatchError::InvalidResource("merge patch is not an object".into())),
+        };
+        check_merge_patch(self, members)?;
+        let mut value = serde_json::to_value(self)
+            .map_err(|e| JsonPatchError::InvalidResource(e.to_string()))?;
+        merge_values(&mut value, merge_patch);
+        if let Some(object) = value.as_object_mut() {
+            // As with `patch`, a resource left without relationships has none
+            let empty = object.get("relationships")
+                .and_then(|relationships| relationships.as_object())
+                .is_some_and(|relationships| relationships.is_empty());
+            if empty {
+                object.remove("relationships");
+            }
+        }
+        let patched: Resource = serde_json::from_value(value)
+            .map_err(|e| JsonPatchError::InvalidResource(e.to_string()))?;
+        let patchset = self.diff_deep(patched.clone()).map_err(diff_error)?;
+        Ok((patched, patchset))
+    }
+
+    /// An RFC 7386 JSON Merge Patch of the `attributes` and relationship `data` turning this
+    /// resource object into `other`
+    pub fn diff_merge_patch(&self, other: &Resource)
+        -> Result<JsonApiValue, DiffPatchError>
+    {
+        if self._type != other._type {
+            return Err(DiffPatchError::IncompatibleTypes(self._type.clone(), other._type.clone()));
+        }
+        let resource_value = |resource: &Resource| {
+            let mut value = serde_json::to_value(resource).unwrap_or(JsonApiValue::Null);
+            if let JsonApiValue::Object(ref mut members) = value {
+                members.retain(|name, _| MERGE_PATCH_MEMBERS.contains(&name.as_str()));
+                let relationships = members.get_mut("relationships")
+                    .and_then(|relationships| relationships.as_object_mut());
+                for relationship in relationships.into_iter().flat_map(|r| r.values_mut()) {
+                    if let JsonApiValue::Object(ref mut relationship) = *relationship {
+                        relationship.retain(|name, _| name == "data");
+                    }
+                }
+            }
+            value
+        };
+        Ok(merge_patch_between(&resource_value(self), &resource_value(other))
+            .unwrap_or_else(|| JsonApiValue::Object(serde_json::Map::new())))
+    }
+}
+
+/// Reject the members of a merge patch of `resource` that a `PatchSet` can not express
+fn check_merge_patch(resource: &Resource, members: &serde_json::Map<String, JsonApiValue>)
+    -> Result<(), JsonPatchError>
+{
+    let unsupported = members.keys().find(|name| !MERGE_PATCH_MEMBERS.contains(&name.as_str()));
+    if let Some(name) = unsupported {
+        return Err(JsonPatchError::Unsupported(format!("/{}", name)));
+    }
+
+    let no_relationships = Relationships::new();
+    let current = resource.relationships.as_ref().unwrap_or(&no_relationships);
+    let null = JsonApiValue::Null;
+    let changes: Vec<(&String, &JsonApiValue)> = match members.get("relationships") {
+        Some(&JsonApiValue::Null) => current.keys().map(|name| (name, &null)).collect(),
+        Some(JsonApiValue::Object(relationships)) => relationships.iter().collect(),
+        _ => Vec::new(),
+    };
+
+    for (name, change) in changes {
+        let pointer = format!("/relationships/{}", pointer_segment(name));
+        match *change {
+            JsonApiValue::Null if current.get(name).is_some_and(|r| r.data.is_none()) => {
+                return Err(JsonPatchError::Unsupported(pointer));
+            }
+            JsonApiValue::Object(ref relationship) => {
+                for (member, value) in relationship {
+                    if member != "data" || value.is_null() {
+                        let member = pointer_segment(member);
+                        return Err(JsonPatchError::Unsupported(
+                            format!("{}/{}", pointer, member)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Apply a merge patch to `target` as in RFC 7386
+fn merge_values(target: &mut JsonApiValue, merge_patch: &JsonApiValue) {
+    let members = match *merge_patch {
+        JsonApiValue::Object(ref members) => members,
+        _ => {
+            *target = merge_patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = JsonApiValue::Object(serde_json::Map::new());
+    }
+    if let JsonApiValue::Object(ref mut object) = *target {
+        for (name, value) in members {
+            if value.is_null() {
+                object.remove(name);
+            } else {
+                merge_values(object.entry(name.clone()).or_insert(JsonApiValue::Null), value);
+            }
+        }
+    }
+}
+
+/// The merge patch turning `previous` into `next`, or `None` when they are equal
+fn merge_patch_between(previous: &JsonApiValue, next: &JsonApiValue) -> Option<JsonApiValue> {
+    if previous == next {
+        return None;
+    }
+    match (previous, next) {
+        (JsonApiValue::Object(previous), JsonApiValue::Object(next)) => {
+            let mut merge_patch = serde_json::Map::new();
+            for name in previous.keys().filter(|name| !next.contains_key(*name)) {
+                merge_patch.insert(name.clone(), JsonApiValue::Null);
+            }
+            for (name, value) in next {
+                let change = match previous.get(name) {
+                    Some(previous) => merge_patch_between(previous, value),
+                    None => Some(value.clone()),
+                };
+                if let Some(change) = change {
+                    merge_patch.insert(name.clone(), change);
+                }
+            }
+            Some(JsonApiValue::Object(merge_patch))
+        }
+        _ => Some(next.clone()),
+    }
+}
+
+fn apply_operation(target: &mut JsonApiValue, operation: &JsonPatchOperation)
+    -> Result<(), JsonPatchError>
+{
+    match *operation {
+        JsonPatchOperation::Add { ref path, ref value } => pointer_add(target, path, value.clone()),
+        JsonPatchOperation::Remove { ref path } => pointer_remove(target, path).map(|_| ()),
+        JsonPatchOperation::Replace { ref path, ref value } => {
+            let current = pointer_get_mut(target, path)?;
+            *current = value.clone();
+            Ok(())
+        }
+        JsonPatchOperation::Move { ref from, ref path } => {
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(JsonPatchError::InvalidPointer(path.clone()));
+            }
+            let value = pointer_remove(target, from)?;
+            pointer_add(target, path, value)
+        }
+        JsonPatchOperation::Copy { ref from, ref path } => {
+            let value = pointer_get_mut(target, from)?.clone();
+            pointer_add(target, path, value)
+        }
+        JsonPatchOperation::Test { ref path, ref value } => {
+            if json_equal(pointer_get_mut(target, path)?, value) {
+                Ok(())
+            } else {
+                Err(JsonPatchError::TestFailed(path.clone()))
+            }
+        }
+    }
+}
+
+/// Equality as for a `test` operation, comparing numbers by value (RFC 6902, section 4.6)
+fn json_equal(a: &JsonApiValue, b: &JsonApiValue) -> bool {
+    match (a, b) {
+        (JsonApiValue::Number(a), JsonApiValue::Number(b)) => {
+            match (a.as_i64(), b.as_i64(), a.as_u64(), b.as_u64()) {
+                (Some(a), Some(b), _, _) => a == b,
+                (_, _, Some(a), Some(b)) => a == b,
+                _ => a.as_f64() == b.as_f64(),
+            }
+        }
+        (JsonApiValue::Array(a), JsonApiValue::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| json_equal(a, b))
+        }
+        (JsonApiValue::Object(a), JsonApiValue::Object(b)) => {
+            a.len() == b.len() &&
+            a.iter().all(|(name, a)| b.get(name).is_some_and(|b| json_equal(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Split a JSON pointer into its unescaped reference tokens
+fn parse_pointer(path: &str) -> Result<Vec<String>, JsonPatchError> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !path.starts_with('/') {
+        return Err(JsonPatchError::InvalidPointer(path.into()));
+    }
+    Ok(path[1..].split('/').map(unescape_pointer_segment).collect())
+}
+
+/// The value at `path`, which must exist
+pub(crate) fn pointer_get_mut<'a>(target: &'a mut JsonApiValue, path: &str)
+    -> Result<&'a mut JsonApiValue, JsonPatchError>
+{
+    let mut current = target;
+    for token in parse_pointer(path)? {
+        current = match *current {
+            JsonApiValue::Object(ref mut object) => object.get_mut(&token),
+            JsonApiValue::Array(ref mut array) => {
+                array_index(&token, array.len()).and_then(move |index| array.get_mut(index))
+            }
+            _ => None,
+        }.ok_or_else(|| JsonPatchError::PathNotFound(path.into()))?;
+    }
+    Ok(current)
+}
+
+/// The parent of the value at `path`, which must exist, and the last reference token
+fn parent_mut<'a>(target: &'a mut JsonApiValue, path: &str)
+    -> Result<(&'a mut JsonApiValue, String), JsonPatchError>
+{
+    let index = path.rfind('/').ok_or_else(|| JsonPatchError::InvalidPointer(path.into()))?;
+    let last = parse_pointer(&path[index..])?.remove(0);
+    Ok((pointer_get_mut(target, &path[..index])?, last))
+}
+
+pub(crate) fn pointer_add(target: &mut JsonApiValue, path: &str, value: JsonApiValue)
+    -> Result<(), JsonPatchError>
+{
+    if path.is_empty() {
+        *target = value;
+        return Ok(());
+    }
+    let (parent, last) = parent_mut(target, path)?;
+    match *parent {
+        JsonApiValue::Object(ref mut object) => {
+            object.insert(last, value);
+            Ok(())
+        }
+        JsonApiValue::Array(ref mut array) => {
+            let index = if last == "-" {
+                array.len()
+            } else {
+                array_index(&last, array.len() + 1)
+                    .ok_or_else(|| JsonPatchError::PathNotFound(path.into()))?
+            };
+            array.insert(index, value);
+            Ok(())
+        }
+        _ => Err(JsonPatchError::PathNotFound(path.into())),
+    }
+}
+
+pub(crate) fn pointer_remove(target: &mut JsonApiValue, path: &str)
+    -> Result<JsonApiValue, JsonPatchError>
+{
+    let (parent, last) = parent_mut(target, path)?;
+    let removed = match *parent {
+        JsonApiValue::Object(ref mut object) => match object.entry(last) {
+            Entry::Occupied(entry) => Some(entry.remove()),
+            Entry::Vacant(_) => None,
+        },
+        JsonApiValue::Array(ref mut array) => {
+            array_index(&last, array.len()).map(|index| array.remove(index))
+        }
+        _ => None,
+    };
+    removed.ok_or_else(|| JsonPatchError::PathNotFound(path.into()))
+}
+
+/// Parse an array index token, which must be below `len`
+fn array_index(token: &str, len: usize) -> Option<usize> {
+    if token.len() > 1 && token.starts_with('0') {
+        return None;
+    }
+    token.parse().ok().filter(|&index| index < len)
+}
@@ -10,7 +10,7 @@ pub struct PageParams {
 /// JSON-API Query parameters
 pub type QueryFields = Option<HashMap<String, Vec<String>>>;
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct Query {
     pub _type: String,
     pub include: Option<Vec<String>>,
@@ -56,18 +56,19 @@ impl Query {
 
                 let mut fields = HashMap::<String, Vec<String>>::new();
 
-                o.find("fields").map(|x| if x.is_object() {
-                    x.as_object().map(|obj| for (key, value) in obj.iter() {
-                        let arr: Vec<String> = match value.as_str() {
-                            Some(string) => string.split(',').map(|s| s.to_string()).collect(),
-                            None => Vec::<String>::new(),
-                        };
-                        fields.insert(key.to_string(), arr);
-
-                    });
-                } else {
-                    error!("Query::from_params : No fields found in {:?}", x);
-                });
+                if let Some(x) = o.find("fields") {
+                    if let Some(obj) = x.as_object() {
+                        for (key, value) in obj.iter() {
+                            let arr: Vec<String> = match value.as_str() {
+                                Some(string) => string.split(',').map(|s| s.to_string()).collect(),
+                                None => Vec::<String>::new(),
+                            };
+                            fields.insert(key.to_string(), arr);
+                        }
+                    } else {
+                        error!("Query::from_params : No fields found in {:?}", x);
+                    }
+                }
 
                 let page = PageParams {
                     number: match o.find_path(&["page", "number"]) {
@@ -126,7 +127,7 @@ impl Query {
 
                 Query {
                     _type: "none".into(),
-                    include: include,
+                    include,
                     fields: Some(fields),
                     page: Some(page),
                 }
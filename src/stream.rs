@@ -0,0 +1,483 @@
+//! Streaming serialization and parsing of documents
+//!
+//! `CollectionWriter` writes a collection document one resource at a time, and `DocumentReader`
+//! reads one back.
+//!
+//! ```
+//! # #[macro_use] extern crate jsonapi;
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate serde_json;
+//! use jsonapi::model::*;
+//! use jsonapi::stream::to_writer;
+//!
+//! #[derive(Debug, Serialize, Deserialize)]
+//! struct Flea {
+//!     id: Option<String>,
+//!     name: String,
+//! }
+//! jsonapi_model!(Flea; "flea");
+//!
+//! # fn main() {
+//! let fleas = (1..4).map(|i| Flea {
+//!     id: Some(i.to_string()),
+//!     name: format!("flea {}", i),
+//! });
+//!
+//! let mut out = Vec::new();
+//! to_writer(&mut out, fleas).unwrap();
+//!
+//! let doc: JsonApiDocument = serde_json::from_slice(&out).unwrap();
+//! assert!(doc.is_valid());
+//! # }
+//! ```
+
+use api::*;
+use builder::{ResourceKey, ResourceSet};
+use errors::*;
+use model::JsonApiModel;
+use query::Query;
+use serde::Serialize;
+use serde_json;
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read, Write};
+
+/// Writes a collection document to an `io::Write`, one primary resource at a time
+///
+/// The document is complete once `finish` has been called. The keys of written primary
+/// resources are kept, to leave them out of `included`.
+#[derive(Debug)]
+pub struct CollectionWriter<W: Write> {
+    writer: W,
+    query: Query,
+    links: Option<Links>,
+    meta: Option<Meta>,
+    jsonapi: Option<JsonApiInfo>,
+    started: bool,
+    primary: HashSet<ResourceKey>,
+    included: ResourceSet,
+}
+
+impl<W: Write> CollectionWriter<W> {
+    pub fn new(writer: W) -> Self {
+        CollectionWriter {
+            writer,
+            query: Query::default(),
+            links: None,
+            meta: None,
+            jsonapi: None,
+            started: false,
+            primary: HashSet::new(),
+            included: ResourceSet::default(),
+        }
+    }
+
+    /// Select the fields and included resources of models written with `write_model`
+    pub fn with_query(mut self, query: Query) -> Self {
+        self.query = query;
+        self
+    }
+
+    pub fn with_links(mut self, links: Links) -> Self {
+        self.links = Some(links);
+        self
+    }
+
+    pub fn with_meta(mut self, meta: Meta) -> Self {
+        self.meta = Some(meta);
+        self
+    }
+
+    pub fn with_jsonapi(mut self, jsonapi: JsonApiInfo) -> Self {
+        self.jsonapi = Some(jsonapi);
+        self
+    }
+
+    /// Write a model as a primary resource, keeping its related resources for `included`
+    pub fn write_model<M: JsonApiModel>(&mut self, model: &M) -> Result<()> {
+        let (resource, included) = model.try_to_jsonapi_resource_with_query(&self.query)?;
+        self.write_resource(&resource)?;
+        for resource in included.unwrap_or_default() {
+            self.include(resource);
+        }
+        Ok(())
+    }
+
+    /// Write a primary resource
+    pub fn write_resource(&mut self, resource: &Resource) -> Result<()> {
+        if self.started {
+            self.writer.write_all(b",")?;
+        } else {
+            self.writer.write_all(b"{\"data\":[")?;
+            self.started = true;
+        }
+        serde_json::to_writer(&mut self.writer, resource)?;
+
+        self.included.remove(resource);
+        self.primary.extend(ResourceKey::all(resource));
+        Ok(())
+    }
+
+    /// Add a resource to `included`, unless it has been written as a primary resource
+    pub fn include(&mut self, resource: Resource) {
+        let primary = ResourceKey::all(&resource).iter().any(|key| self.primary.contains(key));
+        if !primary {
+            self.included.insert(resource);
+        }
+    }
+
+    /// Write `included` and the top-level members, completing the document, and return the
+    /// underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        if !self.started {
+            self.writer.write_all(b"{\"data\":[")?;
+        }
+        self.writer.write_all(b"]")?;
+
+        let included = self.included.into_resources();
+        if !included.is_empty() {
+            write_member(&mut self.writer, "included", &included)?;
+        }
+        if let Some(ref links) = self.links {
+            write_member(&mut self.writer, "links", links)?;
+        }
+        if let Some(ref meta) = self.meta {
+            write_member(&mut self.writer, "meta", meta)?;
+        }
+        if let Some(ref jsonapi) = self.jsonapi {
+            write_member(&mut self.writer, "jsonapi", jsonapi)?;
+        }
+
+        self.writer.write_all(b"}")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Write a top-level member following the ones already written
+fn write_member<W: Write, T: Serialize>(writer: &mut W, name: &str, value: &T) -> Result<()> {
+    write!(writer, ",\"{}\":", name)?;
+    Ok(serde_json::to_writer(writer, value)?)
+}
+
+/// Write the models produced by an iterator as a collection document
+pub fn to_writer<W, M, I>(writer: W, models: I) -> Result<W>
+where
+    W: Write,
+    M: JsonApiModel,
+    I: IntoIterator<Item = M>,
+{
+    to_writer_with_query(writer, models, &Default::default())
+}
+
+/// Write the models produced by an iterator as a collection document, with fields and included
+/// resources selected by `query`
+pub fn to_writer_with_query<W, M, I>(writer: W, models: I, query: &Query) -> Result<W>
+where
+    W: Write,
+    M: JsonApiModel,
+    I: IntoIterator<Item = M>,
+{
+    let mut collection = CollectionWriter::new(writer).with_query(query.clone());
+    for model in models {
+        collection.write_model(&model)?;
+    }
+    collection.finish()
+}
+
+/// A resource read by a `DocumentReader`, with the member it was found in
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentItem {
+    /// A resource from `data`
+    Data(Resource),
+    /// A resource from `included`
+    Included(Resource),
+}
+
+impl DocumentItem {
+    pub fn resource(&self) -> &Resource {
+        match *self {
+            DocumentItem::Data(ref resource) | DocumentItem::Included(ref resource) => resource,
+        }
+    }
+
+    pub fn into_resource(self) -> Resource {
+        match self {
+            DocumentItem::Data(resource) | DocumentItem::Included(resource) => resource,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReaderState {
+    Start,
+    Members { first: bool },
+    Data { first: bool },
+    Included { first: bool },
+    Done,
+}
+
+/// Reads the resources of a document from an `io::Read` one at a time
+///
+/// Yields the resources in `data` and `included` in document order; other top-level members
+/// are collected into `document()`. Error columns are byte offsets.
+///
+/// ```
+/// use jsonapi::stream::{DocumentItem, DocumentReader};
+///
+/// let json = r#"{
+///   "data": [{ "type": "articles", "id": "1" }, { "type": "articles", "id": "2" }],
+///   "included": [{ "type": "people", "id": "9" }],
+///   "meta": { "total": 2 }
+/// }"#;
+///
+/// let mut reader = DocumentReader::new(json.as_bytes());
+/// let items: Vec<DocumentItem> = reader.by_ref().map(|item| item.unwrap()).collect();
+///
+/// assert_eq!(items.len(), 3);
+/// assert_eq!(items[2].resource()._type, "people");
+/// assert!(reader.document().meta.is_some());
+/// ```
+#[derive(Debug)]
+pub struct DocumentReader<R: Read> {
+    reader: BufReader<R>,
+    state: ReaderState,
+    document: JsonApiDocument,
+    /// Line and column of the last byte consumed, as in `errors::Error::Parse`. Columns count
+    /// bytes, not characters.
+    line: usize,
+    column: usize,
+}
+
+impl<R: Read> DocumentReader<R> {
+    pub fn new(reader: R) -> Self {
+        DocumentReader {
+            reader: BufReader::new(reader),
+            state: ReaderState::Start,
+            document: JsonApiDocument::default(),
+            line: 1,
+            column: 0,
+        }
+    }
+
+    /// The top-level members read so far, other than `data` and `included`. `data` is set to
+    /// `PrimaryData::None` when it was `null`.
+    pub fn document(&self) -> &JsonApiDocument {
+        &self.document
+    }
+
+    /// Read the rest of the document and return its top-level members, see `document`
+    pub fn into_document(mut self) -> Result<JsonApiDocument> {
+        for item in self.by_ref() {
+            item?;
+        }
+        Ok(self.document)
+    }
+
+    fn next_item(&mut self) -> Result<Option<DocumentItem>> {
+        loop {
+            match self.state {
+                ReaderState::Start => {
+                    self.expect(b'{')?;
+                    self.state = ReaderState::Members { first: true };
+                }
+                ReaderState::Members { first } => {
+                    if self.peek()? == Some(b'}') {
+                        self.consume();
+                        if self.peek()?.is_some() {
+                            let (line, column) = (self.line, self.column + 1);
+                            let message = "trailing characters".into();
+                            return Err(Error::Parse { message, line, column });
+                        }
+                        self.state = ReaderState::Done;
+                        continue;
+                    }
+                    if !first {
+                        self.expect(b',')?;
+                    }
+                    let name: String = self.read_value()?;
+                    self.expect(b':')?;
+                    self.state = ReaderState::Members { first: false };
+                    if let Some(item) = self.read_member(&name)? {
+                        return Ok(Some(item));
+                    }
+                }
+                ReaderState::Data { first } | ReaderState::Included { first } => {
+                    if self.peek()? == Some(b']') {
+                        self.consume();
+                        self.state = ReaderState::Members { first: false };
+                        continue;
+                    }
+                    if !first {
+                        self.expect(b',')?;
+                    }
+                    let resource = self.read_value()?;
+                    return Ok(Some(match self.state {
+                        ReaderState::Data { .. } => {
+                            self.state = ReaderState::Data { first: false };
+                            DocumentItem::Data(resource)
+                        }
+                        _ => {
+                            self.state = ReaderState::Included { first: false };
+                            DocumentItem::Included(resource)
+                        }
+                    }));
+                }
+                ReaderState::Done => return Ok(None),
+            }
+        }
+    }
+
+    /// Read the value of a top-level member, returning the resource it holds for a single
+    /// resource in `data`
+    fn read_member(&mut self, name: &str) -> Result<Option<DocumentItem>> {
+        match name {
+            "data" => match self.peek()? {
+                Some(b'[') => {
+                    self.consume();
+                    self.state = ReaderState::Data { first: true };
+                }
+                Some(b'n') => {
+                    let _: serde_json::Value = self.read_value()?;
+                    self.document.data = Some(PrimaryData::None);
+                }
+                _ => return Ok(Some(DocumentItem::Data(self.read_value()?))),
+            },
+            "included" => {
+                self.expect(b'[')?;
+                self.state = ReaderState::Included { first: true };
+            }
+            "links" => self.document.links = self.read_value()?,
+            "meta" => self.document.meta = self.read_value()?,
+            "errors" => self.document.errors = self.read_value()?,
+            "jsonapi" => self.document.jsonapi = self.read_value()?,
+            _ => {
+                let _: serde_json::Value = self.read_value()?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read the next JSON value and deserialize it
+    fn read_value<T: ::serde::de::DeserializeOwned>(&mut self) -> Result<T> {
+        let mut raw = Vec::new();
+        self.skip_whitespace()?;
+        let (line, column) = (self.line, self.column);
+        self.capture_value(&mut raw)?;
+        serde_json::from_slice(&raw).map_err(|error| match Error::from(error) {
+            // Locate the error in the whole document rather than in the value
+            Error::Parse { message, line: 1, column: value_column } => {
+                Error::Parse { message, line, column: column + value_column }
+            }
+            Error::Parse { message, line: value_line, column } => {
+                Error::Parse { message, line: line + value_line - 1, column }
+            }
+            error => error,
+        })
+    }
+
+    /// Copy the bytes of the next JSON value into `raw`, without interpreting them
+    fn capture_value(&mut self, raw: &mut Vec<u8>) -> Result<()> {
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        self.skip_whitespace()?;
+        loop {
+            let byte = match self.peek_raw()? {
+                Some(byte) => byte,
+                None if depth == 0 && !in_string && !raw.is_empty() => return Ok(()),
+                None => return Err(self.parse_error("unexpected end of document")),
+            };
+
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if byte == b'\\' {
+                    escaped = true;
+                } else if byte == b'"' {
+                    in_string = false;
+                }
+            } else {
+                match byte {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' if depth == 0 => return Ok(()),
+                    b'}' | b']' => depth -= 1,
+                    b',' if depth == 0 => return Ok(()),
+                    b' ' | b'\t' | b'\n' | b'\r' if depth == 0 => return Ok(()),
+                    _ => {}
+                }
+            }
+
+            raw.push(byte);
+            self.consume();
+
+            if depth == 0 && !in_string && matches!(byte, b'"' | b'}' | b']') {
+                return Ok(());
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.peek()? {
+            Some(byte) if byte == expected => {
+                self.consume();
+                Ok(())
+            }
+            Some(byte) => {
+                let message = format!("expected '{}', found '{}'", expected as char, byte as char);
+                Err(Error::Parse { message, line: self.line, column: self.column + 1 })
+            }
+            None => Err(self.parse_error("unexpected end of document")),
+        }
+    }
+
+    /// The next byte that is not whitespace
+    fn peek(&mut self) -> Result<Option<u8>> {
+        self.skip_whitespace()?;
+        self.peek_raw()
+    }
+
+    fn skip_whitespace(&mut self) -> Result<()> {
+        while let Some(byte) = self.peek_raw()? {
+            if !matches!(byte, b' ' | b'\t' | b'\n' | b'\r') {
+                break;
+            }
+            self.consume();
+        }
+        Ok(())
+    }
+
+    fn peek_raw(&mut self) -> Result<Option<u8>> {
+        let buffer = self.reader.fill_buf()?;
+        Ok(buffer.first().cloned())
+    }
+
+    fn consume(&mut self) {
+        if self.reader.buffer().first() == Some(&b'\n') {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        self.reader.consume(1);
+    }
+
+    fn parse_error(&self, message: &str) -> Error {
+        Error::Parse { message: message.into(), line: self.line, column: self.column }
+    }
+}
+
+impl<R: Read> Iterator for DocumentReader<R> {
+    type Item = Result<DocumentItem>;
+
+    fn next(&mut self) -> Option<Result<DocumentItem>> {
+        match self.next_item() {
+            Ok(item) => item.map(Ok),
+            Err(error) => {
+                self.state = ReaderState::Done;
+                Some(Err(error))
+            }
+        }
+    }
+}
@@ -1,8 +1,11 @@
+#![allow(clippy::assertions_on_constants, clippy::bool_assert_comparison)]
+
 extern crate jsonapi;
 extern crate serde_json;
 extern crate env_logger;
 
 use jsonapi::api::*;
+use std::str::FromStr;
 
 mod helper;
 use helper::read_json_file;
@@ -13,6 +16,7 @@ fn it_works() {
     let resource = Resource {
         _type: "test".into(),
         id: Some("123".into()),
+        lid: None,
         attributes: ResourceAttributes::new(),
         relationships: Some(Relationships::new()),
         links: None,
@@ -67,6 +71,7 @@ fn jsonapi_document_can_be_valid() {
     let resource = Resource {
         _type: "test".into(),
         id: Some("123".into()),
+        lid: None,
         attributes: ResourceAttributes::new(),
         relationships: Some(Relationships::new()),
         links: None,
@@ -98,6 +103,7 @@ fn jsonapi_document_invalid_errors() {
     let resource = Resource {
         _type: "test".into(),
         id: Some("123".into()),
+        lid: None,
         attributes: ResourceAttributes::new(),
         relationships: Some(Relationships::new()),
         links: None,
@@ -107,6 +113,7 @@ fn jsonapi_document_invalid_errors() {
     let included_resource = Resource {
         _type: "test".into(),
         id: Some("123".into()),
+        lid: None,
         attributes: ResourceAttributes::new(),
         relationships: Some(Relationships::new()),
         links: None,
@@ -468,7 +475,12 @@ fn can_diff_resource() {
                         }
                         Ok(patchset) => {
                             println!("can_diff_resource: PatchSet is {:?}", patchset);
-                            assert_eq!(patchset.patches.len(), 5);
+                            assert_eq!(patchset.patches.len(), 6);
+                            let author = &patchset.patches[5];
+                            assert_eq!(author.patch_type, PatchType::Relationship);
+                            assert_eq!(author.operation, PatchOperation::Replace);
+                            assert_eq!(author.subject, "author");
+                            assert_eq!(author.next["id"], "10");
                         }
                     }
                 }
@@ -483,6 +495,7 @@ fn it_omits_empty_document_and_primary_data_keys() {
     let resource = Resource {
         _type: "test".into(),
         id: Some("123".into()),
+        lid: None,
         attributes: ResourceAttributes::new(),
         ..Default::default()
     };
@@ -530,3 +543,666 @@ fn it_allows_for_optional_attributes() {
     let data: Result<JsonApiDocument, serde_json::Error> = serde_json::from_str(serialized);
     assert_eq!(data.is_ok(), true);
 }
+
+#[test]
+fn can_read_link_objects() {
+    let _ = env_logger::init();
+    let s = ::read_json_file("data/links_002.json");
+    let links: Links = serde_json::from_str(&s).unwrap();
+
+    match links.related() {
+        Some(Link::Object(object)) => {
+            assert_eq!(object.href, "http://example.com/articles/1/comments");
+            assert_eq!(object.meta.as_ref().unwrap().get("count"), Some(&serde_json::json!(10)));
+        }
+        _ => assert!(false),
+    }
+    assert_eq!(links.self_link(), None);
+}
+
+#[test]
+fn can_read_link_strings() {
+    let _ = env_logger::init();
+    let s = ::read_json_file("data/links_001.json");
+    let links: Links = serde_json::from_str(&s).unwrap();
+
+    assert_eq!(links.self_link(), Some(&Link::Href("http://example.com/posts".into())));
+    assert_eq!(links.self_link().and_then(|l| l.href()), Some("http://example.com/posts"));
+    assert_eq!(links.self_link().and_then(|l| l.meta()), None);
+}
+
+#[test]
+fn links_round_trip_losslessly() {
+    let _ = env_logger::init();
+    let s = ::read_json_file("data/links_003.json");
+    let links: Links = serde_json::from_str(&s).unwrap();
+
+    assert_eq!(links.get("prev"), Some(&Link::None));
+    assert_eq!(links.prev(), None);
+    assert_eq!(links.next().and_then(|l| l.href()),
+               Some("http://example.com/articles?page[number]=2"));
+
+    let describedby = links.describedby().and_then(|l| l.as_object()).unwrap();
+    assert_eq!(describedby.rel, Some("describedby".into()));
+    assert_eq!(describedby._type, Some("application/schema+json".into()));
+    assert_eq!(describedby.title, Some("Articles schema".into()));
+    assert_eq!(describedby.describedby,
+               Some(Box::new(Link::Href("http://example.com/schemas/link".into()))));
+    assert_eq!(describedby.extra.get("version:schema"), Some(&serde_json::json!("draft-07")));
+
+    let original: serde_json::Value = serde_json::from_str(&s).unwrap();
+    assert_eq!(serde_json::to_value(&links).unwrap(), original);
+}
+
+#[test]
+fn can_get_pagination_from_links() {
+    let _ = env_logger::init();
+    let s = ::read_json_file("data/pagination.json");
+    let doc: JsonApiDocument = serde_json::from_str(&s).unwrap();
+    let pagination = doc.links.unwrap().pagination();
+
+    assert_eq!(pagination.first,
+               Some("http://example.com/articles?page[number]=1&page[size]=1".into()));
+    assert_eq!(pagination.last,
+               Some("http://example.com/articles?page[number]=13&page[size]=1".into()));
+}
+
+#[test]
+fn can_deserialize_relationships_without_data() {
+    let _ = env_logger::init();
+    let s = ::read_json_file("data/relationships_without_data.json");
+    let doc = JsonApiDocument::from_str(&s).unwrap();
+    assert!(doc.is_valid());
+
+    let resource = match doc.data {
+        Some(PrimaryData::Single(resource)) => resource,
+        _ => panic!("Expected a single resource"),
+    };
+
+    let comments = resource.get_relationship("comments").unwrap();
+    assert_eq!(comments.data, None);
+    assert_eq!(comments.as_id(), Ok(None));
+    assert!(comments.links.as_ref().unwrap().related().is_some());
+
+    let tags = resource.get_relationship("tags").unwrap();
+    assert_eq!(tags.data, None);
+    assert!(tags.meta.is_some());
+
+    let editor = resource.get_relationship("editor").unwrap();
+    assert_eq!(editor.data, Some(IdentifierData::None));
+}
+
+#[test]
+fn ids_of_relationships_with_local_ids() {
+    let _ = env_logger::init();
+    let relationship: Relationship = serde_json::from_str(r#"{
+      "data": [{ "type": "comments", "id": "5" }, { "type": "comments", "lid": "new" }]
+    }"#).unwrap();
+    assert_eq!(relationship.as_ids(), Err(RelationshipAssumptionError::MissingId));
+
+    let relationship: Relationship =
+        serde_json::from_str(r#"{ "data": { "type": "people", "lid": "new" } }"#).unwrap();
+    assert_eq!(relationship.as_id(), Err(RelationshipAssumptionError::MissingId));
+}
+
+#[test]
+fn relationship_data_absent_and_null_round_trip() {
+    let _ = env_logger::init();
+    let absent = Relationship {
+        meta: Some(Meta::new()),
+        ..Default::default()
+    };
+    let null = Relationship {
+        data: Some(IdentifierData::None),
+        ..Default::default()
+    };
+
+    assert_eq!(serde_json::to_string(&absent).unwrap(), r#"{"meta":{}}"#);
+    assert_eq!(serde_json::to_string(&null).unwrap(), r#"{"data":null}"#);
+    assert_eq!(serde_json::from_str::<Relationship>(r#"{"meta":{}}"#).unwrap(), absent);
+    assert_eq!(serde_json::from_str::<Relationship>(r#"{"data":null}"#).unwrap(), null);
+}
+
+#[test]
+fn relationship_without_content_is_invalid() {
+    let _ = env_logger::init();
+    let mut relationships = Relationships::new();
+    relationships.insert("author".into(), Relationship::default());
+    let resource = Resource {
+        _type: "articles".into(),
+        id: Some("1".into()),
+        relationships: Some(relationships),
+        ..Default::default()
+    };
+    let doc = JsonApiDocument {
+        data: Some(PrimaryData::Multiple(vec![resource])),
+        ..Default::default()
+    };
+
+    match doc.validate() {
+        None => assert!(false),
+        Some(errors) => {
+            assert_eq!(errors,
+                       vec![DocumentValidationError::EmptyRelationship(
+                           "/data/0/relationships/author".into())]);
+        }
+    }
+}
+
+#[test]
+fn can_deserialize_jsonapi_info_ext_and_profile() {
+    let _ = env_logger::init();
+    let serialized = r#"{
+            "version": "1.1",
+            "ext": ["https://jsonapi.org/ext/atomic"],
+            "profile": ["http://example.com/profiles/flexible-pagination"]
+        }"#;
+    let info: JsonApiInfo = serde_json::from_str(serialized).unwrap();
+    assert_eq!(info.version, Some("1.1".into()));
+    assert_eq!(info.ext, Some(vec!["https://jsonapi.org/ext/atomic".to_string()]));
+    assert_eq!(info.profile,
+               Some(vec!["http://example.com/profiles/flexible-pagination".to_string()]));
+}
+
+#[test]
+fn validation_errors_are_located_by_json_pointer() {
+    let _ = env_logger::init();
+    let s = ::read_json_file("data/invalid_document.json");
+    let doc = JsonApiDocument::from_str(&s).unwrap();
+
+    let errors = doc.validate().unwrap();
+    assert_eq!(errors,
+               vec![DocumentValidationError::InvalidMemberName(
+                        "/data/0/attributes/_private".into()),
+                    DocumentValidationError::ReservedFieldName(
+                        "/data/0/attributes/links".into()),
+                    DocumentValidationError::InvalidMemberName(
+                        "/data/0/attributes/settings/font.size".into()),
+                    DocumentValidationError::ReservedFieldName(
+                        "/data/0/attributes/settings/links".into()),
+                    DocumentValidationError::InvalidMemberName(
+                        "/data/0/attributes/settings/unit:size".into()),
+                    DocumentValidationError::FieldNameCollision(
+                        "/data/0/relationships/author".into()),
+                    DocumentValidationError::DuplicateResource("/included/1".into()),
+                    DocumentValidationError::DuplicateLocalId("/included/3".into()),
+                    DocumentValidationError::InvalidJsonApiObject("/jsonapi/version".into()),
+                    DocumentValidationError::InvalidJsonApiObject("/jsonapi/ext/1".into())]);
+
+    let response_errors = doc.validate_response().unwrap();
+    assert_eq!(response_errors.last(),
+               Some(&DocumentValidationError::MissingResourceId("/included/3".into())));
+}
+
+#[test]
+fn validation_error_as_jsonapi_error() {
+    let error = DocumentValidationError::ReservedFieldName("/data/attributes/type".into());
+    let jsonapi_error = error.to_jsonapi_error();
+    assert_eq!(jsonapi_error.status, Some("400".into()));
+    assert_eq!(jsonapi_error.source.unwrap().pointer, Some("/data/attributes/type".into()));
+    assert_eq!(DocumentValidationError::MissingContent.pointer(), "");
+}
+
+#[test]
+fn document_with_only_meta_is_valid() {
+    let mut meta = Meta::new();
+    meta.insert("total".into(), serde_json::json!(0));
+    let doc = JsonApiDocument {
+        meta: Some(meta),
+        ..Default::default()
+    };
+    assert!(doc.is_valid());
+}
+
+#[test]
+fn checks_member_names() {
+    assert!(is_valid_member_name("title"));
+    assert!(is_valid_member_name("first name"));
+    assert!(is_valid_member_name("@context"));
+    assert!(is_valid_member_name("naïve"));
+    assert!(!is_valid_member_name(""));
+    assert!(!is_valid_member_name("-title"));
+    assert!(!is_valid_member_name("title "));
+    assert!(!is_valid_member_name("a/b"));
+    assert!(!is_valid_member_name("price$"));
+    assert!(!is_valid_member_name("ext:title"));
+    assert!(is_valid_extension_member_name("ext:title"));
+    assert!(!is_valid_extension_member_name("ext:"));
+    assert!(!is_valid_extension_member_name("@ext:title"));
+}
+
+#[test]
+fn can_get_typed_attributes() {
+    let s = ::read_json_file("data/resource_all_attributes.json");
+    let resource = Resource::from_str(&s).unwrap();
+
+    assert_eq!(resource.get_attribute_as::<String>("title"), Ok("Rails is Omakase".to_string()));
+    assert_eq!(resource.get_attribute_as::<u32>("likes"), Ok(250));
+    assert_eq!(resource.get_attribute_as::<bool>("published"), Ok(true));
+    assert_eq!(resource.get_attribute_as::<Vec<String>>("tags"),
+               Ok(vec!["rails".to_string(), "news".to_string()]));
+
+    assert_eq!(resource.get_attribute_as::<String>("likes"),
+               Err(JsonApiDataError::IncompatibleAttributeType("likes".into())));
+    assert_eq!(resource.get_attribute_as::<String>("body"),
+               Err(JsonApiDataError::AttributeNotFound("body".into())));
+    assert_eq!(resource.get_attribute_as::<String>("title.text"),
+               Err(JsonApiDataError::AttributeNotFound("title.text".into())));
+}
+
+#[test]
+fn can_set_typed_attributes() {
+    let mut resource = Resource {
+        _type: "people".into(),
+        id: Some("9".into()),
+        ..Default::default()
+    };
+
+    resource.set_attribute("name", "Dan").unwrap();
+    resource.set_attribute("address.city", "Amsterdam").unwrap();
+    resource.set_attribute("address.geo.lat", 52.37).unwrap();
+
+    assert_eq!(resource.get_attribute_as::<String>("name"), Ok("Dan".to_string()));
+    assert_eq!(resource.get_attribute_path("address.city"), Some(&serde_json::json!("Amsterdam")));
+    assert_eq!(resource.get_attribute_as::<f64>("address.geo.lat"), Ok(52.37));
+
+    assert_eq!(resource.set_attribute("name.first", "Dan"),
+               Err(JsonApiDataError::IncompatibleAttributeType("name".into())));
+    assert_eq!(resource.set_attribute("address.city.name", "Amsterdam"),
+               Err(JsonApiDataError::IncompatibleAttributeType("address.city".into())));
+    assert_eq!(resource.get_attribute_as::<String>("name"), Ok("Dan".to_string()));
+}
+
+#[test]
+fn can_diff_added_and_removed_attributes() {
+    let before = Resource::from_str(r#"{
+      "type": "people", "id": "9",
+      "attributes": { "name": "Dan", "twitter": "dgeb" }
+    }"#).unwrap();
+    let after = Resource::from_str(r#"{
+      "type": "people", "id": "9",
+      "attributes": { "name": "Dan", "age": 42 }
+    }"#).unwrap();
+
+    let patchset = before.diff(after.clone()).unwrap();
+    assert_eq!(patchset.patches, vec![
+        Patch::attribute(PatchOperation::Add, "age", serde_json::Value::Null,
+                         serde_json::json!(42)),
+        Patch::attribute(PatchOperation::Remove, "twitter", serde_json::json!("dgeb"),
+                         serde_json::Value::Null),
+    ]);
+
+    assert_eq!(before.clone().patch(patchset).unwrap(), after);
+}
+
+#[test]
+fn can_diff_relationships() {
+    let before = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "relationships": {
+        "author": { "data": { "type": "people", "id": "9" } },
+        "comments": { "data": [
+          { "type": "comments", "id": "5" },
+          { "type": "comments", "id": "12" },
+          { "type": "comments", "id": "13" }
+        ] },
+        "editor": { "data": null },
+        "tags": { "data": [] },
+        "related": { "links": { "related": "http://example.com/articles/1/related" } }
+      }
+    }"#).unwrap();
+    let after = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "relationships": {
+        "author": { "data": { "type": "people", "id": "9" } },
+        "comments": { "data": [
+          { "type": "comments", "id": "12" },
+          { "type": "comments", "id": "14" }
+        ] },
+        "editor": { "data": { "type": "people", "id": "2" } },
+        "related": { "links": { "related": "http://example.com/articles/1/other" } }
+      }
+    }"#).unwrap();
+
+    let patchset = before.diff(after.clone()).unwrap();
+    let summary: Vec<(PatchOperation, &str)> = patchset.patches
+        .iter()
+        .map(|patch| (patch.operation, patch.subject.as_str()))
+        .collect();
+    assert_eq!(summary, vec![
+        (PatchOperation::Remove, "comments/2"),
+        (PatchOperation::Remove, "comments/0"),
+        (PatchOperation::Add, "comments/-"),
+        (PatchOperation::Replace, "editor"),
+        (PatchOperation::Remove, "tags"),
+    ]);
+    assert!(patchset.patches.iter().all(|patch| patch.patch_type == PatchType::Relationship));
+    assert_eq!(patchset.patches[2].next, serde_json::json!({ "type": "comments", "id": "14" }));
+
+    let patched = before.clone().patch(patchset).unwrap();
+    assert_eq!(patched.get_relationship("comments"), after.get_relationship("comments"));
+    assert_eq!(patched.get_relationship("editor"), after.get_relationship("editor"));
+    assert!(patched.get_relationship("tags").is_none());
+}
+
+#[test]
+fn diff_round_trips_reordered_and_linked_relationships() {
+    let before = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "relationships": {
+        "comments": { "data": [
+          { "type": "comments", "id": "5" },
+          { "type": "comments", "id": "12" }
+        ] },
+        "author": { "links": { "related": "http://example.com/articles/1/author" } }
+      }
+    }"#).unwrap();
+    let after = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "relationships": {
+        "comments": { "data": [
+          { "type": "comments", "id": "12" },
+          { "type": "comments", "id": "5" }
+        ] },
+        "author": {
+          "links": { "related": "http://example.com/articles/1/author" },
+          "data": { "type": "people", "id": "9" }
+        }
+      }
+    }"#).unwrap();
+
+    let patchset = before.diff(after.clone()).unwrap();
+    assert_eq!(patchset.patches.len(), 2);
+    assert_eq!(before.clone().patch(patchset).unwrap(), after);
+}
+
+#[test]
+fn diff_rejects_removing_data_of_remaining_relationship() {
+    let before = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "relationships": {
+        "author": {
+          "links": { "related": "http://example.com/articles/1/author" },
+          "data": { "type": "people", "id": "9" }
+        }
+      }
+    }"#).unwrap();
+    let mut after = before.clone();
+    after.relationships.as_mut().unwrap().get_mut("author").unwrap().data = None;
+
+    assert_eq!(before.diff(after), Err(DiffPatchError::UnsupportedChange("author".into())));
+}
+
+#[test]
+fn can_diff_nested_attribute_values() {
+    let before = Resource::from_str(r#"{
+      "type": "people", "id": "9",
+      "attributes": {
+        "name": "Dan",
+        "settings": {
+          "theme": { "color": "dark", "font": "mono" },
+          "notifications": ["email", "sms", "push"],
+          "a/b": 1
+        }
+      }
+    }"#).unwrap();
+    let after = Resource::from_str(r#"{
+      "type": "people", "id": "9",
+      "attributes": {
+        "name": "Dan",
+        "settings": {
+          "theme": { "color": "light" },
+          "notifications": ["email"],
+          "a/b": 2,
+          "language": "en"
+        }
+      }
+    }"#).unwrap();
+
+    let patchset = before.diff_deep(after.clone()).unwrap();
+    let summary: Vec<(PatchOperation, &str)> = patchset.patches
+        .iter()
+        .map(|patch| (patch.operation, patch.subject.as_str()))
+        .collect();
+    assert_eq!(summary, vec![
+        (PatchOperation::Replace, "settings/a~1b"),
+        (PatchOperation::Add, "settings/language"),
+        (PatchOperation::Remove, "settings/notifications/2"),
+        (PatchOperation::Remove, "settings/notifications/1"),
+        (PatchOperation::Replace, "settings/theme/color"),
+        (PatchOperation::Remove, "settings/theme/font"),
+    ]);
+    assert!(patchset.patches.iter().all(|patch| patch.patch_type == PatchType::Attribute));
+
+    assert_eq!(before.clone().patch(patchset).unwrap(), after);
+
+    let shallow = before.diff(after).unwrap();
+    assert_eq!(shallow.patches.len(), 1);
+    assert_eq!(shallow.patches[0].subject, "settings");
+}
+
+#[test]
+fn can_patch_nested_array_additions() {
+    let before = Resource::from_str(r#"{
+      "type": "people", "id": "9",
+      "attributes": { "tags": [1, { "a": 1 }] }
+    }"#).unwrap();
+    let mut after = before.clone();
+    after.attributes.insert("tags".into(), serde_json::json!([1, { "a": 2 }, 3, 4]));
+
+    let patchset = before.diff_deep(after.clone()).unwrap();
+    assert_eq!(patchset.patches.iter().map(|p| p.subject.as_str()).collect::<Vec<_>>(),
+               vec!["tags/1/a", "tags/-", "tags/-"]);
+    assert_eq!(before.clone().patch(patchset).unwrap(), after);
+
+    let mut missing = before.clone();
+    missing.attributes.clear();
+    assert!(missing.patch(before.diff_deep(after).unwrap()).is_err());
+}
+
+#[test]
+fn strict_patch_detects_concurrent_changes() {
+    let original = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "attributes": { "title": "Rails is Omakase", "likes": 250, "settings": { "a": 1 } },
+      "relationships": {
+        "author": { "data": { "type": "people", "id": "9" } },
+        "comments": { "data": [
+          { "type": "comments", "id": "5" },
+          { "type": "comments", "id": "12" }
+        ] }
+      }
+    }"#).unwrap();
+
+    let mut mine = original.clone();
+    mine.attributes.insert("likes".into(), serde_json::json!(251));
+    mine.set_attribute("settings.a", 2).unwrap();
+    mine.attributes.insert("draft".into(), serde_json::json!(true));
+    mine.relationships.as_mut().unwrap().insert("comments".into(), Relationship {
+        data: Some(IdentifierData::Multiple(vec![ResourceIdentifier {
+            _type: "comments".into(),
+            id: Some("12".into()),
+            ..Default::default()
+        }])),
+        ..Default::default()
+    });
+    let patchset = original.diff_deep(mine.clone()).unwrap();
+
+    assert_eq!(original.patch_strict(patchset.clone()), Ok(mine.clone()));
+    assert_eq!(mine.patch_strict(patchset.clone()),
+               Err(DiffPatchError::IncorrectPropertyValue("draft".into())));
+
+    let mut theirs = original.clone();
+    theirs.attributes.insert("likes".into(), serde_json::json!(300));
+    assert_eq!(theirs.patch_strict(patchset.clone()),
+               Err(DiffPatchError::IncorrectPropertyValue("likes".into())));
+
+    let mut theirs = original.clone();
+    theirs.attributes.remove("settings");
+    assert_eq!(theirs.patch_strict(patchset.clone()),
+               Err(DiffPatchError::NonExistentProperty("settings/a".into())));
+
+    let mut theirs = original.clone();
+    theirs.attributes.insert("draft".into(), serde_json::json!(false));
+    assert_eq!(theirs.patch_strict(patchset.clone()),
+               Err(DiffPatchError::IncorrectPropertyValue("draft".into())));
+
+    let mut theirs = original.clone();
+    theirs.relationships = None;
+    assert_eq!(theirs.patch_strict(patchset.clone()),
+               Err(DiffPatchError::NonExistentProperty("comments/0".into())));
+
+    let mut other_type = original.clone();
+    other_type._type = "posts".into();
+    assert_eq!(other_type.patch_strict(patchset.clone()),
+               Err(DiffPatchError::IncompatibleTypes("posts".into(), "articles".into())));
+
+    let mut other_id = original.clone();
+    other_id.id = Some("2".into());
+    assert_eq!(other_id.patch_strict(patchset.clone()),
+               Err(DiffPatchError::IncompatibleIds("2".into(), "1".into())));
+
+    let mut unsaved = original.clone();
+    unsaved.id = None;
+    assert_eq!(unsaved.patch_strict(patchset), Err(DiffPatchError::MissingId));
+}
+
+#[test]
+fn strict_patch_checks_relationship_data() {
+    let original = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "relationships": { "author": { "data": { "type": "people", "id": "9" } } }
+    }"#).unwrap();
+    let mut mine = original.clone();
+    mine.relationships = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "relationships": { "author": { "data": { "type": "people", "id": "10" } } }
+    }"#).unwrap().relationships;
+    let mut theirs = original.clone();
+    theirs.relationships = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "relationships": { "author": { "data": null } }
+    }"#).unwrap().relationships;
+
+    let patchset = original.diff(mine.clone()).unwrap();
+    assert_eq!(original.patch_strict(patchset.clone()), Ok(mine));
+    assert_eq!(theirs.patch_strict(patchset),
+               Err(DiffPatchError::IncorrectPropertyValue("author".into())));
+}
+
+#[test]
+fn inverted_patches_undo_changes() {
+    let before = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "attributes": {
+        "title": "Rails is Omakase", "draft": true,
+        "tags": ["rails", "ruby", "opinion"],
+        "settings": { "theme": "dark" }
+      },
+      "relationships": {
+        "comments": { "data": [
+          { "type": "comments", "id": "5" },
+          { "type": "comments", "id": "12" },
+          { "type": "comments", "id": "13" }
+        ] },
+        "editor": { "data": null },
+        "tags": { "data": [] }
+      }
+    }"#).unwrap();
+    let after = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "attributes": {
+        "title": "Rails was Omakase", "likes": 1,
+        "tags": ["rails"],
+        "settings": { "theme": "light", "font": "serif" }
+      },
+      "relationships": {
+        "comments": { "data": [
+          { "type": "comments", "id": "12" },
+          { "type": "comments", "id": "14" },
+          { "type": "comments", "id": "15" }
+        ] },
+        "editor": { "data": { "type": "people", "id": "2" } }
+      }
+    }"#).unwrap();
+
+    let patchsets = [before.diff(after.clone()).unwrap(), before.diff_deep(after.clone()).unwrap()];
+    for patchset in &patchsets {
+        let inverted = patchset.invert();
+        assert_eq!(inverted.invert(), *patchset);
+        assert_eq!(after.patch_strict(inverted.clone()), Ok(before.clone()));
+        assert_eq!(after.clone().patch(inverted).unwrap(), before);
+    }
+}
+
+#[test]
+fn inverted_patches_remove_added_relationships() {
+    let before = Resource::from_str(r#"{
+      "type": "articles", "id": "1", "attributes": { "title": "Rails is Omakase" }
+    }"#).unwrap();
+    let after = Resource::from_str(r#"{
+      "type": "articles", "id": "1", "attributes": { "title": "Rails is Omakase" },
+      "relationships": { "author": { "data": { "type": "people", "id": "9" } } }
+    }"#).unwrap();
+
+    let patchset = before.diff(after.clone()).unwrap();
+    let undone = after.clone().patch(patchset.invert()).unwrap();
+    assert_eq!(undone.relationships, None);
+    assert_eq!(undone, before);
+    assert_eq!(after.patch_strict(patchset.invert()), Ok(before));
+}
+
+#[test]
+fn inverted_patches_check_previous_values() {
+    let before = Resource::from_str(r#"{
+      "type": "articles", "id": "1", "attributes": { "tags": ["rails"] }
+    }"#).unwrap();
+    let mut after = before.clone();
+    after.set_attribute("tags", vec!["rails", "ruby"]).unwrap();
+
+    let mut changed = after.clone();
+    changed.set_attribute("tags", vec!["rails", "opinion"]).unwrap();
+
+    let inverted = before.diff_deep(after).unwrap().invert();
+    assert_eq!(inverted.patches[0].operation, PatchOperation::Remove);
+    assert_eq!(inverted.patches[0].subject, "tags/-");
+    assert_eq!(changed.patch_strict(inverted),
+               Err(DiffPatchError::IncorrectPropertyValue("tags/-".into())));
+}
+
+#[test]
+fn patchset_round_trips_through_json() {
+    let before = Resource::from_str(r#"{
+      "type": "articles", "id": "1", "attributes": { "title": "Rails is Omakase" }
+    }"#).unwrap();
+    let mut after = before.clone();
+    after.set_attribute("title", "Rails was Omakase").unwrap();
+
+    let patchset = before.diff(after).unwrap();
+    let json = serde_json::to_value(&patchset).unwrap();
+    assert_eq!(json, serde_json::json!({
+        "resource_type": "articles",
+        "resource_id": "1",
+        "patches": [{
+            "patch_type": "attribute",
+            "operation": "replace",
+            "subject": "title",
+            "previous": "Rails is Omakase",
+            "next": "Rails was Omakase"
+        }]
+    }));
+    assert_eq!(serde_json::from_value::<PatchSet>(json).unwrap(), patchset);
+}
+
+#[test]
+fn patchset_needs_an_id() {
+    let draft = Resource::from_str(r#"{
+      "type": "articles", "lid": "a", "attributes": { "title": "Draft" }
+    }"#).unwrap();
+    let mut edited = draft.clone();
+    edited.set_attribute("title", "Rails is Omakase").unwrap();
+
+    assert!(matches!(PatchSet::try_new_for(&draft),
+                     Err(jsonapi::errors::Error::MissingId { .. })));
+    assert_eq!(draft.diff(edited), Err(DiffPatchError::MissingId));
+}
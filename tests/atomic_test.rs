@@ -0,0 +1,242 @@
+extern crate jsonapi;
+extern crate serde_json;
+
+use jsonapi::api::*;
+use jsonapi::atomic::*;
+
+mod helper;
+use helper::read_json_file;
+
+/// Store assigning sequential ids and recording what it was asked to do
+#[derive(Default)]
+struct MemoryStore {
+    next_id: usize,
+    resources: Vec<Resource>,
+    calls: Vec<String>,
+    committed: bool,
+    rolled_back: bool,
+}
+
+impl AtomicStore for MemoryStore {
+    fn add(&mut self, mut resource: Resource) -> Result<Resource, JsonApiError> {
+        if resource.attributes.contains_key("invalid") {
+            return Err(JsonApiError {
+                status: Some("422".into()),
+                source: Some(ErrorSource {
+                    pointer: Some("/data/attributes/invalid".into()),
+                    parameter: None,
+                }),
+                ..Default::default()
+            });
+        }
+        self.next_id += 1;
+        resource.id = Some(self.next_id.to_string());
+        self.calls.push(format!("add {} {}", resource._type, self.next_id));
+        self.resources.push(resource.clone());
+        Ok(resource)
+    }
+
+    fn update(&mut self, resource: Resource) -> Result<Option<Resource>, JsonApiError> {
+        self.calls.push(format!("update {} {}", resource._type, resource.id.unwrap()));
+        Ok(None)
+    }
+
+    fn remove(&mut self, target: &ResourceIdentifier) -> Result<(), JsonApiError> {
+        self.calls.push(format!("remove {} {}", target._type, target.id.clone().unwrap()));
+        Ok(())
+    }
+
+    fn update_relationship(&mut self,
+                           target: &ResourceIdentifier,
+                           relationship: &str,
+                           data: IdentifierData)
+                           -> Result<(), JsonApiError> {
+        let id = match data {
+            IdentifierData::Single(identifier) => identifier.id.unwrap(),
+            _ => "null".into(),
+        };
+        self.calls.push(format!("update {} {} {} {}",
+                                target._type,
+                                target.id.clone().unwrap(),
+                                relationship,
+                                id));
+        Ok(())
+    }
+
+    fn add_to_relationship(&mut self,
+                           target: &ResourceIdentifier,
+                           relationship: &str,
+                           data: ResourceIdentifiers)
+                           -> Result<(), JsonApiError> {
+        let ids: Vec<String> = data.into_iter().map(|i| i.id.unwrap()).collect();
+        self.calls.push(format!("add {} {} {} {}",
+                                target._type,
+                                target.id.clone().unwrap(),
+                                relationship,
+                                ids.join(",")));
+        Ok(())
+    }
+
+    fn remove_from_relationship(&mut self,
+                                target: &ResourceIdentifier,
+                                relationship: &str,
+                                _data: ResourceIdentifiers)
+                                -> Result<(), JsonApiError> {
+        self.calls.push(format!("remove {} {} {}",
+                                target._type,
+                                target.id.clone().unwrap(),
+                                relationship));
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<(), JsonApiError> {
+        self.committed = true;
+        Ok(())
+    }
+
+    fn rollback(&mut self) {
+        self.rolled_back = true;
+    }
+}
+
+fn operations() -> AtomicDocument {
+    serde_json::from_str(&read_json_file("data/atomic_operations.json"))
+        .expect("AtomicDocument should be read from atomic_operations.json")
+}
+
+#[test]
+fn can_deserialize_operations() {
+    let doc = operations();
+    let ops = doc.operations.unwrap();
+    assert_eq!(ops.len(), 5);
+
+    assert_eq!(ops[0].op, OperationCode::Add);
+    match ops[0].data {
+        Some(OperationData::Resource(ref resource)) => {
+            assert_eq!(resource.lid, Some("order-1".into()))
+        }
+        _ => panic!("Expected a resource"),
+    }
+
+    assert!(ops[2].targets_relationship());
+    match ops[2].data {
+        Some(OperationData::Identifiers(IdentifierData::Multiple(ref identifiers))) => {
+            assert_eq!(identifiers[0].lid, Some("item-1".into()))
+        }
+        _ => panic!("Expected resource identifiers"),
+    }
+
+    assert!(ops[3].targets_relationship());
+    assert_eq!(ops[4].op, OperationCode::Remove);
+    assert_eq!(ops[4].data, None);
+}
+
+#[test]
+fn operations_round_trip() {
+    let original: serde_json::Value =
+        serde_json::from_str(&read_json_file("data/atomic_operations.json")).unwrap();
+    let doc: AtomicDocument = serde_json::from_value(original.clone()).unwrap();
+    let ops = serde_json::to_value(&doc).unwrap()["atomic:operations"].clone();
+
+    assert_eq!(ops[2], original["atomic:operations"][2]);
+    assert_eq!(ops[3], original["atomic:operations"][3]);
+    assert_eq!(ops[4], original["atomic:operations"][4]);
+}
+
+#[test]
+fn executes_operations_in_order_resolving_lids() {
+    let mut store = MemoryStore::default();
+    let results = execute(&mut store, &operations()).expect("Operations should succeed");
+
+    assert_eq!(store.calls,
+               vec!["add orders 1",
+                    "add line-items 2",
+                    "add orders 1 line-items 2",
+                    "update orders 1 customer 42",
+                    "remove carts 13"]);
+    assert!(store.committed);
+
+    let item = &store.resources[1];
+    let order = item.get_relationship("order").unwrap();
+    assert_eq!(order.as_id(), Ok(Some(&"1".to_string())));
+
+    let json = serde_json::to_value(&results).unwrap();
+    assert_eq!(json["atomic:results"][0]["data"]["id"], "1");
+    assert_eq!(json["atomic:results"][1]["data"]["id"], "2");
+    assert_eq!(json["atomic:results"][2], serde_json::json!({}));
+    assert_eq!(json["jsonapi"]["ext"], serde_json::json!([EXTENSION_URI]));
+}
+
+#[test]
+fn failing_operation_yields_error_document() {
+    let mut doc = operations();
+    if let Some(OperationData::Resource(ref mut resource)) =
+        doc.operations.as_mut().unwrap()[1].data {
+        resource.attributes.insert("invalid".into(), serde_json::json!(true));
+    }
+
+    let mut store = MemoryStore::default();
+    let error_doc = execute(&mut store, &doc).expect_err("The second operation should fail");
+    assert!(store.rolled_back);
+    assert!(!store.committed);
+
+    let errors = error_doc.errors.unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].status, Some("422".into()));
+    assert_eq!(errors[0].source.as_ref().unwrap().pointer,
+               Some("/atomic:operations/1/data/attributes/invalid".into()));
+}
+
+#[test]
+fn unknown_lid_is_rejected() {
+    let doc: AtomicDocument = serde_json::from_str(r#"{
+        "atomic:operations": [{
+            "op": "remove",
+            "ref": { "type": "orders", "lid": "missing" }
+        }]
+    }"#).unwrap();
+
+    let mut store = MemoryStore::default();
+    let error_doc = execute(&mut store, &doc).expect_err("The lid is unknown");
+    let errors = error_doc.errors.unwrap();
+    assert_eq!(errors[0].status, Some("400".into()));
+    assert_eq!(errors[0].source.as_ref().unwrap().pointer,
+               Some("/atomic:operations/0".into()));
+    assert!(store.calls.is_empty());
+}
+
+#[test]
+fn target_conflicting_with_data_is_rejected() {
+    let doc: AtomicDocument = serde_json::from_str(r#"{
+        "atomic:operations": [{
+            "op": "update",
+            "ref": { "type": "articles", "id": "1" },
+            "data": { "type": "articles", "id": "2", "attributes": { "title": "Rails" } }
+        }, {
+            "op": "update",
+            "ref": { "type": "articles", "id": "1" },
+            "data": { "type": "people", "id": "1" }
+        }]
+    }"#).unwrap();
+
+    for (index, member) in ["id", "type"].iter().enumerate() {
+        let mut doc = doc.clone();
+        doc.operations.as_mut().unwrap().remove(1 - index);
+        let mut store = MemoryStore::default();
+        let errors = execute(&mut store, &doc).unwrap_err().errors.unwrap();
+        assert_eq!(errors[0].status, Some("400".into()));
+        assert_eq!(errors[0].source.as_ref().unwrap().pointer,
+                   Some(format!("/atomic:operations/0/data/{}", member)));
+        assert!(store.calls.is_empty());
+    }
+}
+
+#[test]
+fn hrefs_target_relationships_as_parsed() {
+    let operation = |href: &str| -> Operation {
+        serde_json::from_value(serde_json::json!({ "op": "update", "href": href })).unwrap()
+    };
+    assert!(operation("/articles/1/relationships/author").targets_relationship());
+    assert!(!operation("/relationships/1").targets_relationship());
+    assert!(!operation("/articles/relationships").targets_relationship());
+}
@@ -0,0 +1,78 @@
+extern crate jsonapi;
+extern crate serde_json;
+
+use jsonapi::api::*;
+use jsonapi::borrowed::*;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+mod helper;
+use helper::read_json_file;
+
+#[test]
+fn converts_to_the_owned_document() {
+    for name in &["data/collection.json", "data/compound_document.json", "data/errors.json",
+                  "data/resource_001.json", "data/links_001.json",
+                  "data/relationships_without_data.json"] {
+        let json = read_json_file(name);
+        let borrowed = BorrowedDocument::parse(&json).unwrap();
+        assert_eq!(borrowed.to_document().unwrap(), JsonApiDocument::from_str(&json).unwrap(),
+                   "{}", name);
+    }
+}
+
+#[test]
+fn borrows_from_the_input() {
+    let json = r#"{
+      "data": {
+        "type": "articles", "id": "1",
+        "attributes": { "title": "JSON API paints my bikeshed!", "tags": ["a", "b"] },
+        "relationships": {
+          "author": { "data": { "type": "people", "id": "9" } }
+        }
+      },
+      "meta": { "copyright": "Copyright 2015 Example Corp." }
+    }"#;
+    let doc = BorrowedDocument::parse(json).unwrap();
+    let article = doc.primary()[0];
+
+    assert!(matches!(article._type, Cow::Borrowed("articles")));
+    assert!(matches!(article.id, Some(Cow::Borrowed("1"))));
+    assert!(article.attributes.keys().all(|name| matches!(*name, Cow::Borrowed(_))));
+    assert_eq!(article.get_attribute::<Vec<String>>("tags").unwrap(),
+               Some(vec!["a".to_string(), "b".to_string()]));
+    assert_eq!(article.get_attribute::<String>("missing").unwrap(), None);
+    assert!(article.get_attribute::<u32>("title").is_err());
+
+    match article.get_relationship("author").unwrap().data {
+        Some(BorrowedIdentifierData::Single(ref identifier)) => {
+            assert_eq!(identifier.id, Some(Cow::Borrowed("9")))
+        }
+        _ => panic!("expected a to-one relationship"),
+    }
+    assert_eq!(doc.meta.unwrap().get(), r#"{ "copyright": "Copyright 2015 Example Corp." }"#);
+}
+
+#[test]
+fn escaped_strings_are_owned() {
+    let json = r#"{ "data": [{ "type": "article\u0073", "id": "1" }] }"#;
+    let doc = BorrowedDocument::parse(json).unwrap();
+
+    assert!(matches!(doc.primary()[0]._type, Cow::Owned(_)));
+    assert_eq!(doc.primary()[0]._type, "articles");
+}
+
+#[test]
+fn null_data() {
+    let doc = BorrowedDocument::parse(r#"{ "data": null }"#).unwrap();
+    assert!(matches!(doc.data, Some(BorrowedPrimaryData::None)));
+    assert!(doc.primary().is_empty());
+    assert_eq!(doc.to_document().unwrap().data, Some(PrimaryData::None));
+}
+
+#[test]
+fn serializes_unchanged_values() {
+    let json = r#"{"data":{"type":"people","id":"9","attributes":{"name":"Dan"}}}"#;
+    let doc = BorrowedDocument::parse(json).unwrap();
+    assert_eq!(serde_json::to_string(&doc).unwrap(), json);
+}
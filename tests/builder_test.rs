@@ -0,0 +1,151 @@
+#[macro_use] extern crate jsonapi;
+#[macro_use] extern crate serde_derive;
+#[macro_use] extern crate serde_json;
+
+use jsonapi::model::*;
+use jsonapi::builder::DocumentBuilder;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Article {
+    id: Option<String>,
+    title: String,
+    author: Person,
+}
+jsonapi_model!(Article; "articles"; has one author);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Person {
+    id: Option<String>,
+    name: String,
+}
+jsonapi_model!(Person; "people");
+
+fn resource(_type: &str, id: &str) -> Resource {
+    Resource {
+        _type: _type.into(),
+        id: Some(id.into()),
+        ..Default::default()
+    }
+}
+
+fn dan() -> Person {
+    Person { id: Some("9".into()), name: "Dan".into() }
+}
+
+#[test]
+fn models_share_included_resources() {
+    let articles = vec![
+        Article { id: Some("1".into()), title: "JSON API paints my bikeshed!".into(), author: dan() },
+        Article { id: Some("2".into()), title: "Rails is Omakase".into(), author: dan() },
+    ];
+
+    let doc = DocumentBuilder::new().models(&articles).build().unwrap();
+
+    match doc.data {
+        Some(PrimaryData::Multiple(ref resources)) => assert_eq!(resources.len(), 2),
+        _ => panic!("expected a collection of articles"),
+    }
+    let included = doc.included.unwrap();
+    assert_eq!(included.len(), 1);
+    assert_eq!(included[0].get_attribute("name"), Some(&json!("Dan")));
+}
+
+#[test]
+fn merges_sparse_copies() {
+    let mut name = resource("people", "9");
+    name.attributes.insert("name".into(), json!("Dan"));
+    let mut twitter = resource("people", "9");
+    twitter.attributes.insert("name".into(), json!("Daniel"));
+    twitter.attributes.insert("twitter".into(), json!("dgeb"));
+
+    let mut article = resource("articles", "1");
+    article.relationships = Some(Relationships::new());
+    article.relationships.as_mut().unwrap().insert("author".into(), Relationship {
+        data: Some(IdentifierData::Single(resource("people", "9").as_identifier())),
+        ..Default::default()
+    });
+
+    let doc = DocumentBuilder::new()
+        .resource(article)
+        .include(name)
+        .include(twitter)
+        .build()
+        .unwrap();
+
+    let included = doc.included.unwrap();
+    assert_eq!(included.len(), 1);
+    assert_eq!(included[0].get_attribute("name"), Some(&json!("Dan")));
+    assert_eq!(included[0].get_attribute("twitter"), Some(&json!("dgeb")));
+}
+
+#[test]
+fn merges_copies_sharing_a_local_id() {
+    let mut saved = resource("people", "9");
+    saved.lid = Some("new-person".into());
+    saved.attributes.insert("name".into(), json!("Dan"));
+    let mut unsaved = Resource {
+        _type: "people".into(),
+        lid: Some("new-person".into()),
+        ..Default::default()
+    };
+    unsaved.attributes.insert("twitter".into(), json!("dgeb"));
+
+    let doc = DocumentBuilder::new()
+        .resource(resource("articles", "1"))
+        .include(unsaved)
+        .include(saved)
+        .build()
+        .unwrap();
+
+    let included = doc.included.unwrap();
+    assert_eq!(included.len(), 1);
+    assert_eq!(included[0].id, Some("9".into()));
+    assert_eq!(included[0].get_attribute("name"), Some(&json!("Dan")));
+    assert_eq!(included[0].get_attribute("twitter"), Some(&json!("dgeb")));
+}
+
+#[test]
+fn leaves_primary_resources_out_of_included() {
+    let doc = DocumentBuilder::new()
+        .resources(vec![resource("people", "9"), resource("people", "2")])
+        .include(resource("people", "2"))
+        .build()
+        .unwrap();
+
+    assert!(doc.included.is_none());
+}
+
+#[test]
+fn top_level_members() {
+    let doc = DocumentBuilder::new()
+        .null_data()
+        .link("self", "http://example.com/articles/1/author")
+        .meta_member("count", json!(0))
+        .jsonapi(JsonApiInfo { version: Some("1.1".into()), ..Default::default() })
+        .build()
+        .unwrap();
+
+    assert_eq!(doc.data, Some(PrimaryData::None));
+    assert_eq!(doc.links.unwrap().self_link().unwrap().href(),
+               Some("http://example.com/articles/1/author"));
+    assert_eq!(doc.meta.unwrap()["count"], json!(0));
+    assert_eq!(doc.jsonapi.unwrap().version, Some("1.1".into()));
+}
+
+#[test]
+fn refuses_invalid_documents() {
+    assert_eq!(DocumentBuilder::new().build().unwrap_err(),
+               vec![DocumentValidationError::MissingContent]);
+
+    let errors = DocumentBuilder::new()
+        .resources(vec![resource("people", "9"), resource("people", "9")])
+        .build()
+        .unwrap_err();
+    assert!(matches!(errors[0], DocumentValidationError::DuplicateResource(_)));
+
+    let errors = DocumentBuilder::new()
+        .resource(Resource { _type: "people".into(), ..Default::default() })
+        .build()
+        .unwrap_err();
+    assert_eq!(errors, vec![DocumentValidationError::MissingResourceId("/data".into())]);
+}
@@ -0,0 +1,91 @@
+extern crate jsonapi;
+#[macro_use] extern crate serde_json;
+
+use jsonapi::api::*;
+use std::str::FromStr;
+
+mod helper;
+use helper::read_json_file;
+
+fn compound_document() -> JsonApiDocument {
+    JsonApiDocument::from_str(&read_json_file("data/compound_document.json")).unwrap()
+}
+
+#[test]
+fn same_document_has_no_changes() {
+    assert!(compound_document().diff(&compound_document()).is_empty());
+}
+
+#[test]
+fn matches_resources_across_data_and_included() {
+    let previous = compound_document();
+    let mut next = compound_document();
+
+    // Move the author from `included` into `data`, changing it
+    let mut included = next.included.take().unwrap();
+    let mut author = included.remove(0);
+    author.set_attribute("twitter", "dgeb_").unwrap();
+    next.data = Some(PrimaryData::Single(Box::new(author)));
+    // Drop comment 5 and add comment 13
+    included.remove(0);
+    included.push(Resource::from_str(r#"{
+      "type": "comments", "id": "13", "attributes": { "body": "Me too" }
+    }"#).unwrap());
+    next.included = Some(included);
+
+    let changes = previous.diff(&next);
+    let ids = |resources: &[Resource]| -> Vec<(String, String)> {
+        resources.iter().map(|r| (r._type.clone(), r.id.clone().unwrap())).collect()
+    };
+    assert_eq!(ids(&changes.added), vec![("comments".to_string(), "13".to_string())]);
+    assert_eq!(ids(&changes.removed), vec![
+        ("articles".to_string(), "1".to_string()),
+        ("comments".to_string(), "5".to_string()),
+    ]);
+    assert_eq!(changes.changed.len(), 1);
+    assert_eq!(changes.changed[0].resource_type, "people");
+    assert_eq!(changes.changed[0].patches[0].subject, "twitter");
+    assert_eq!(changes.changed[0].patches[0].next, json!("dgeb_"));
+}
+
+#[test]
+fn deep_changes() {
+    let previous = JsonApiDocument::from_str(r#"{
+      "data": [{ "type": "articles", "id": "1", "attributes": { "settings": { "a": 1, "b": 2 } } }]
+    }"#).unwrap();
+    let next = JsonApiDocument::from_str(r#"{
+      "data": [{ "type": "articles", "id": "1", "attributes": { "settings": { "a": 1, "b": 3 } } }]
+    }"#).unwrap();
+
+    assert_eq!(previous.diff(&next).changed[0].patches[0].subject, "settings");
+    assert_eq!(previous.diff_deep(&next).changed[0].patches[0].subject, "settings/b");
+}
+
+#[test]
+fn resources_without_id_are_left_out() {
+    let previous = JsonApiDocument::from_str(r#"{ "data": null }"#).unwrap();
+    let next = JsonApiDocument::from_str(r#"{
+      "data": { "type": "articles", "lid": "a", "attributes": { "title": "Draft" } }
+    }"#).unwrap();
+    assert!(previous.diff(&next).is_empty());
+}
+
+#[test]
+fn reports_resources_that_fail_to_diff() {
+    let previous = JsonApiDocument::from_str(r#"{
+      "data": [{ "type": "articles", "id": "1", "relationships": {
+        "author": { "data": { "type": "people", "id": "9" }, "links": { "related": "/author" } }
+      } }]
+    }"#).unwrap();
+    let next = JsonApiDocument::from_str(r#"{
+      "data": [{ "type": "articles", "id": "1", "relationships": {
+        "author": { "links": { "related": "/author" } }
+      } }]
+    }"#).unwrap();
+
+    let changes = previous.diff(&next);
+    assert!(!changes.is_empty());
+    assert_eq!(changes.failed.len(), 1);
+    assert_eq!(changes.failed[0].0.id, Some("1".into()));
+    assert_eq!(changes.failed[0].1, DiffPatchError::UnsupportedChange("author".into()));
+}
@@ -0,0 +1,113 @@
+#[macro_use] extern crate jsonapi;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+
+use jsonapi::errors::Error;
+use jsonapi::model::*;
+use jsonapi::stream::DocumentReader;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Article {
+    id: Option<String>,
+    title: String,
+}
+jsonapi_model!(Article; "articles");
+
+#[test]
+fn parse_errors_have_line_and_column() {
+    match Resource::from_str("{\n  \"type\": \"articles\",\n  \"id\": 1\n}") {
+        Err(Error::Parse { line, column, ref message }) => {
+            assert_eq!((line, column), (3, 9));
+            assert!(message.starts_with("invalid type: integer `1`"), "{}", message);
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn reader_errors_are_located_in_the_document() {
+    let json = "{\n  \"data\": [\n    { \"type\": \"articles\", \"id\": 1 }\n  ]\n}";
+    let error = DocumentReader::new(json.as_bytes()).find_map(|item| item.err()).unwrap();
+    assert!(matches!(error, Error::Parse { line: 3, column: 33, .. }), "{:?}", error);
+
+    let error = DocumentReader::new("{ \"data\": [".as_bytes())
+        .find_map(|item| item.err())
+        .unwrap();
+    assert_eq!(error.to_string(),
+               "Error parsing document: unexpected end of document at line 1 column 11");
+}
+
+#[test]
+fn missing_data() {
+    let doc = JsonApiDocument::from_str(r#"{ "data": null }"#).unwrap();
+    let error = Article::from_jsonapi_document(&doc).unwrap_err();
+    assert!(matches!(error, Error::MissingData));
+
+    let jsonapi_error = error.to_jsonapi_error();
+    assert_eq!(jsonapi_error.status, Some("400".into()));
+    assert_eq!(jsonapi_error.code, Some("missing_data".into()));
+}
+
+#[test]
+fn conversion_errors_as_jsonapi_errors() {
+    let doc = JsonApiDocument::from_str(r#"{
+      "data": { "type": "articles", "id": "1", "attributes": { "body": "..." } }
+    }"#).unwrap();
+    let error = Article::from_jsonapi_document(&doc).unwrap_err();
+    assert!(matches!(error, Error::Conversion { .. }));
+
+    let jsonapi_error: JsonApiError = error.into();
+    assert_eq!(jsonapi_error.status, Some("422".into()));
+    assert_eq!(jsonapi_error.code, Some("conversion_error".into()));
+    assert_eq!(jsonapi_error.source.unwrap().pointer, Some("/data/attributes/title".into()));
+}
+
+#[test]
+fn invalid_documents() {
+    let doc = JsonApiDocument::from_str(r#"{ "included": [] }"#).unwrap();
+    let error: Error = doc.validate().unwrap().remove(0).into();
+    assert!(matches!(error, Error::InvalidDocument { ref pointer, .. } if pointer == "/included"));
+    assert_eq!(error.to_jsonapi_error().source.unwrap().pointer, Some("/included".into()));
+}
+
+#[test]
+fn patch_conflicts_and_invalid_patches() {
+    let mut article = Resource::from_str(r#"{
+      "type": "articles", "id": "1",
+      "attributes": { "tags": ["rails"] },
+      "relationships": { "author": { "data": { "type": "people", "id": "9" } } }
+    }"#).unwrap();
+    let patch = |patch: Patch| {
+        let mut patchset = PatchSet::new_for(&article);
+        patchset.push(patch);
+        patchset
+    };
+
+    let missing = patch(Patch::attribute(PatchOperation::Remove, "title/0",
+                                         "Rails".into(), JsonApiValue::Null));
+    let wrong_type = patch(Patch::relationship(PatchOperation::Remove, "author/0",
+                                               JsonApiValue::Null, JsonApiValue::Null));
+    let bad_index = patch(Patch::relationship(PatchOperation::Remove, "author/first",
+                                              JsonApiValue::Null, JsonApiValue::Null));
+    let bad_data = patch(Patch::relationship(PatchOperation::Replace, "author",
+                                             JsonApiValue::Null, 42.into()));
+
+    let error = article.patch(missing).unwrap_err();
+    assert!(matches!(error, Error::Patch { .. }), "{:?}", error);
+    assert_eq!(error.status(), 409);
+    for patchset in &[wrong_type, bad_index, bad_data] {
+        let error = article.patch(patchset.clone()).unwrap_err();
+        assert!(matches!(error, Error::InvalidPatch { .. }), "{:?}", error);
+        assert_eq!(error.to_jsonapi_error().status, Some("400".into()));
+        assert_eq!(error.code(), "invalid_patch");
+    }
+}
+
+#[test]
+fn is_a_std_error() {
+    let error: Box<dyn std::error::Error> =
+        Box::new(JsonApiDocument::from_str("[").unwrap_err());
+    assert!(error.to_string().starts_with("Error parsing document: EOF"));
+    assert!(error.source().is_none());
+}
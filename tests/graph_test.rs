@@ -0,0 +1,86 @@
+extern crate jsonapi;
+extern crate serde_json;
+
+use jsonapi::api::*;
+use jsonapi::graph::DocumentGraph;
+use std::str::FromStr;
+
+mod helper;
+use helper::read_json_file;
+
+fn compound_document() -> JsonApiDocument {
+    JsonApiDocument::from_str(&read_json_file("data/compound_document.json")).unwrap()
+}
+
+fn ids(resources: &[&Resource]) -> Vec<String> {
+    resources.iter().map(|r| r.id.clone().unwrap()).collect()
+}
+
+#[test]
+fn indexes_data_and_included() {
+    let doc = compound_document();
+    let graph = DocumentGraph::new(&doc);
+
+    assert_eq!(graph.len(), 4);
+    assert_eq!(ids(graph.primary()), vec!["1"]);
+    assert_eq!(ids(graph.included()), vec!["9", "5", "12"]);
+    assert_eq!(graph.find("people", "9").unwrap().get_attribute("twitter"),
+               Some(&serde_json::json!("dgeb")));
+    assert_eq!(graph.find("people", "2"), None);
+}
+
+#[test]
+fn resolves_relationships() {
+    let doc = compound_document();
+    let graph = DocumentGraph::new(&doc);
+    let article = graph.primary()[0];
+
+    assert_eq!(ids(&graph.related(article, "comments")), vec!["5", "12"]);
+    assert_eq!(ids(&graph.related(article, "author")), vec!["9"]);
+    assert!(graph.related(article, "tags").is_empty());
+
+    let comment = graph.find("comments", "5").unwrap();
+    assert!(graph.related(comment, "author").is_empty());
+}
+
+#[test]
+fn iterates_resources_by_type() {
+    let doc = compound_document();
+    let graph = DocumentGraph::new(&doc);
+
+    let comments: Vec<&Resource> = graph.resources_of_type("comments").collect();
+    assert_eq!(ids(&comments), vec!["5", "12"]);
+    assert_eq!(graph.resources_of_type("tags").count(), 0);
+}
+
+#[test]
+fn walks_include_paths() {
+    let doc = compound_document();
+    let graph = DocumentGraph::new(&doc);
+
+    assert_eq!(ids(&graph.walk("comments")), vec!["5", "12"]);
+    assert_eq!(ids(&graph.walk("comments.author")), vec!["9"]);
+    assert!(graph.walk("comments.author.comments").is_empty());
+
+    let article = graph.primary()[0];
+    assert_eq!(ids(&graph.walk_from(article, "comments.author")), vec!["9"]);
+    assert_eq!(ids(&graph.walk_from(article, "author")), vec!["9"]);
+    assert_eq!(ids(&graph.walk("")), vec!["1"]);
+    assert_eq!(ids(&graph.walk_from(article, "")), vec!["1"]);
+}
+
+#[test]
+fn resolves_local_ids() {
+    let doc = JsonApiDocument::from_str(r#"{
+        "data": {
+            "type": "articles", "lid": "a1",
+            "relationships": { "author": { "data": { "type": "people", "lid": "p1" } } }
+        },
+        "included": [{ "type": "people", "lid": "p1", "attributes": { "name": "Dan" } }]
+    }"#).unwrap();
+    let graph = DocumentGraph::new(&doc);
+
+    let authors = graph.walk("author");
+    assert_eq!(authors.len(), 1);
+    assert_eq!(authors[0].lid, Some("p1".into()));
+}
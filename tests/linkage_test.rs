@@ -0,0 +1,80 @@
+extern crate jsonapi;
+extern crate serde_json;
+
+use jsonapi::api::*;
+use std::str::FromStr;
+
+mod helper;
+use helper::read_json_file;
+
+fn person(id: &str) -> Resource {
+    Resource {
+        _type: "people".into(),
+        id: Some(id.into()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn compound_document_is_fully_linked() {
+    let doc = JsonApiDocument::from_str(&read_json_file("data/compound_document.json")).unwrap();
+    let linkage = doc.linkage();
+
+    assert!(linkage.is_fully_linked());
+    assert_eq!(linkage.dangling.len(), 1);
+    assert_eq!(linkage.dangling[0].0, "/included/1/relationships/author/data");
+    assert_eq!(linkage.dangling[0].1.id, Some("2".into()));
+}
+
+#[test]
+fn reports_and_prunes_orphans() {
+    let mut doc = JsonApiDocument::from_str(&read_json_file("data/compound_document.json"))
+        .unwrap();
+    doc.included.as_mut().unwrap().push(person("404"));
+
+    assert_eq!(doc.linkage().orphans, vec!["/included/3".to_string()]);
+
+    let pruned = doc.prune_orphans();
+    assert_eq!(pruned, vec![person("404")]);
+    assert_eq!(doc.included.as_ref().unwrap().len(), 3);
+    assert!(doc.linkage().is_fully_linked());
+}
+
+#[test]
+fn resources_reached_through_included_are_linked() {
+    let doc = JsonApiDocument::from_str(r#"{
+        "data": [{
+            "type": "articles", "id": "1",
+            "relationships": { "comments": { "data": [{ "type": "comments", "lid": "c1" }] } }
+        }],
+        "included": [
+            { "type": "people", "id": "9" },
+            {
+                "type": "comments", "lid": "c1",
+                "relationships": { "author": { "data": { "type": "people", "id": "9" } } }
+            }
+        ]
+    }"#).unwrap();
+
+    let linkage = doc.linkage();
+    assert!(linkage.is_fully_linked());
+    assert!(linkage.dangling.is_empty());
+}
+
+#[test]
+fn pruning_all_included_drops_included() {
+    let mut doc = JsonApiDocument {
+        data: Some(PrimaryData::Single(Box::new(Resource {
+            _type: "articles".into(),
+            id: Some("1".into()),
+            ..Default::default()
+        }))),
+        included: Some(vec![person("1"), person("2")]),
+        ..Default::default()
+    };
+
+    assert_eq!(doc.linkage().orphans,
+               vec!["/included/0".to_string(), "/included/1".to_string()]);
+    assert_eq!(doc.prune_orphans().len(), 2);
+    assert_eq!(doc.included, None);
+}
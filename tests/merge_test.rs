@@ -0,0 +1,158 @@
+extern crate jsonapi;
+#[macro_use] extern crate serde_json;
+
+use jsonapi::api::*;
+use jsonapi::merge::*;
+use std::str::FromStr;
+
+fn article(json: &str) -> Resource {
+    Resource::from_str(json).unwrap()
+}
+
+fn base() -> Resource {
+    article(r#"{
+      "type": "articles", "id": "1",
+      "attributes": {
+        "title": "Rails is Omakase", "likes": 250,
+        "settings": { "theme": "dark", "font": "serif" },
+        "tags": ["rails", "ruby", "opinion"]
+      },
+      "relationships": {
+        "author": { "data": { "type": "people", "id": "9" } },
+        "comments": { "data": [
+          { "type": "comments", "id": "5" },
+          { "type": "comments", "id": "12" },
+          { "type": "comments", "id": "13" }
+        ] }
+      }
+    }"#)
+}
+
+fn changed(change: &dyn Fn(&mut Resource)) -> PatchSet {
+    let mut resource = base();
+    change(&mut resource);
+    base().diff_deep(resource).unwrap()
+}
+
+#[test]
+fn merges_changes_to_different_members() {
+    let ours = changed(&|article| {
+        article.set_attribute("settings.theme", "light").unwrap();
+        article.set_attribute("tags", json!(["rails", "ruby"])).unwrap();
+    });
+    let theirs = changed(&|article| {
+        article.set_attribute("settings.font", "sans").unwrap();
+        article.set_attribute("tags", json!(["Rails", "ruby", "opinion", "web"])).unwrap();
+        article.relationships.as_mut().unwrap().remove("author");
+    });
+
+    let merged = base().patch_strict(ours.merge(&theirs).unwrap()).unwrap();
+    assert_eq!(merged.get_attribute("settings").unwrap(),
+               &json!({ "theme": "light", "font": "sans" }));
+    assert_eq!(merged.get_attribute("tags").unwrap(), &json!(["Rails", "ruby", "web"]));
+    assert!(merged.get_relationship("author").is_none());
+}
+
+#[test]
+fn identical_changes_are_merged_once() {
+    let change = |article: &mut Resource| {
+        article.attributes.insert("likes".into(), 251.into());
+    };
+    let merged = changed(&change).merge(&changed(&change)).unwrap();
+    assert_eq!(merged, changed(&change));
+}
+
+#[test]
+fn reports_conflicts_with_both_values() {
+    let ours = changed(&|article| {
+        article.attributes.insert("title".into(), "Rails was Omakase".into());
+        article.set_attribute("settings.theme", "light").unwrap();
+    });
+    let theirs = changed(&|article| {
+        article.attributes.insert("title".into(), "Rails is not Omakase".into());
+        article.attributes.insert("settings".into(), json!({ "theme": "blue" }));
+    });
+
+    let conflicts = match ours.merge(&theirs) {
+        Err(MergeError::Conflicts(conflicts)) => conflicts,
+        other => panic!("expected conflicts, got {:?}", other),
+    };
+    let names: Vec<&str> = conflicts.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["settings", "title"]);
+
+    let title = &conflicts[1];
+    assert_eq!(title.ours[0].next, "Rails was Omakase");
+    assert_eq!(title.theirs[0].next, "Rails is not Omakase");
+}
+
+#[test]
+fn relationship_conflicts() {
+    let ours = changed(&|article| {
+        article.relationships.as_mut().unwrap().remove("author");
+    });
+    let theirs = changed(&|article| {
+        let author = Relationship {
+            data: Some(IdentifierData::Single(ResourceIdentifier {
+                _type: "people".into(),
+                id: Some("10".into()),
+                lid: None,
+                meta: None,
+            })),
+            links: None,
+            meta: None,
+        };
+        article.relationships.as_mut().unwrap().insert("author".into(), author);
+    });
+
+    match ours.merge(&theirs) {
+        Err(MergeError::Conflicts(conflicts)) => {
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].patch_type, PatchType::Relationship);
+            assert_eq!(conflicts[0].name, "author");
+        }
+        other => panic!("expected conflicts, got {:?}", other),
+    }
+}
+
+#[test]
+fn resolves_conflicts() {
+    let ours = changed(&|article| {
+        article.attributes.insert("title".into(), "Rails was Omakase".into());
+    });
+    let theirs = changed(&|article| {
+        article.attributes.insert("title".into(), "Rails is not Omakase".into());
+        article.attributes.insert("likes".into(), 251.into());
+    });
+
+    let title = |patches: &PatchSet| {
+        base().patch_strict(patches.clone()).unwrap().get_attribute("title").unwrap().clone()
+    };
+
+    assert_eq!(title(&ours.merge_with(&theirs, |_| Resolution::Ours).unwrap()),
+               "Rails was Omakase");
+    assert_eq!(title(&ours.merge_with(&theirs, |_| Resolution::Theirs).unwrap()),
+               "Rails is not Omakase");
+    assert_eq!(title(&ours.merge_with(&theirs, |_| Resolution::Neither).unwrap()),
+               "Rails is Omakase");
+
+    let custom = ours.merge_with(&theirs, |conflict| {
+        let mut patch = conflict.ours[0].clone();
+        patch.next = "Rails is sometimes Omakase".into();
+        Resolution::Use(vec![patch])
+    }).unwrap();
+    let merged = base().patch_strict(custom).unwrap();
+    assert_eq!(merged.get_attribute("title").unwrap(), "Rails is sometimes Omakase");
+    assert_eq!(merged.get_attribute("likes").unwrap(), 251);
+}
+
+#[test]
+fn different_resources() {
+    let ours = changed(&|article| {
+        article.attributes.insert("likes".into(), 251.into());
+    });
+    let mut theirs = ours.clone();
+    theirs.resource_id = "2".into();
+
+    assert_eq!(ours.merge(&theirs),
+               Err(MergeError::DifferentResources("articles/1".into(), "articles/2".into())));
+}
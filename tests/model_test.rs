@@ -1,8 +1,12 @@
+#![allow(clippy::assertions_on_constants, clippy::bool_assert_comparison)]
+
 #[macro_use] extern crate jsonapi;
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate pretty_assertions;
+extern crate serde;
 extern crate serde_json;
 use jsonapi::model::*;
+use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct Dog {
@@ -58,7 +62,7 @@ fn to_jsonapi_document_and_back(){
     let doc = dog.to_jsonapi_document();
     let json = serde_json::to_string(&doc).unwrap();
     let dog_doc: JsonApiDocument = serde_json::from_str(&json)
-        .expect("Dog JsonApiDocument should be created from the dog json");;
+        .expect("Dog JsonApiDocument should be created from the dog json");
     let dog_again = Dog::from_jsonapi_document(&dog_doc)
         .expect("Dog should be generated from the dog_doc");
 
@@ -131,7 +135,7 @@ fn from_str_to_jsonapi_document() {
         }
     }"#;
 
-    let dog_doc: JsonApiDocument = serde_json::from_str(&dog)
+    let dog_doc: JsonApiDocument = serde_json::from_str(dog)
         .expect("Dog JsonApiDocument should be created from the dog json");
     LonelyDog::from_jsonapi_document(&dog_doc)
         .expect("Dog should be generated from the dog_doc");
@@ -156,3 +160,156 @@ fn omits_relationships_if_empty() {
     assert_eq!(json,
       r#"{"data":{"type":"dog","id":"1","attributes":{"name":"fido"}}}"#);
 }
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Owner {
+    id: Option<String>,
+    local_id: Option<String>,
+    name: String,
+    dogs: Vec<NewDog>,
+}
+jsonapi_model!(Owner; "owner"; lid local_id; has many dogs);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct NewDog {
+    id: Option<String>,
+    local_id: Option<String>,
+    name: String,
+}
+jsonapi_model!(NewDog; "new_dog"; lid local_id);
+
+#[test]
+fn links_unsaved_resources_by_lid() {
+    let owner = Owner {
+        id: None,
+        local_id: Some("owner-1".into()),
+        name: "alice".into(),
+        dogs: vec![
+            NewDog { id: None, local_id: Some("dog-1".into()), name: "fido".into() },
+            NewDog { id: Some("7".into()), local_id: None, name: "rex".into() },
+        ],
+    };
+
+    let doc = owner.to_jsonapi_document();
+    let json = serde_json::to_value(&doc).unwrap();
+    assert_eq!(json["data"]["lid"], "owner-1");
+    assert_eq!(json["data"]["id"], serde_json::Value::Null);
+    assert_eq!(json["data"]["attributes"].get("local_id"), None);
+    assert_eq!(json["data"]["relationships"]["dogs"]["data"],
+               serde_json::json!([
+                   { "type": "new_dog", "lid": "dog-1" },
+                   { "type": "new_dog", "id": "7" }
+               ]));
+
+    let owner_doc: JsonApiDocument = serde_json::from_value(json).unwrap();
+    let owner_again = Owner::from_jsonapi_document(&owner_doc)
+        .expect("Owner should be generated from the owner_doc");
+    assert_eq!(owner_again.local_id, Some("owner-1".into()));
+    assert_eq!(owner_again.dogs.len(), 2);
+    assert_eq!(owner_again.dogs[0].name, "fido");
+    assert_eq!(owner_again.dogs[0].local_id, Some("dog-1".into()));
+    assert_eq!(owner_again.dogs[1].local_id, None);
+    assert_eq!(owner_again.dogs[1].id, Some("7".into()));
+}
+
+#[test]
+fn resource_identifier_meta_round_trips() {
+    let json = r#"{"type":"people","id":"9","meta":{"role":"admin"}}"#;
+    let identifier: ResourceIdentifier = serde_json::from_str(json).unwrap();
+    assert_eq!(identifier.id, Some("9".into()));
+    assert_eq!(identifier.meta.as_ref().unwrap().get("role"),
+               Some(&serde_json::json!("admin")));
+    assert_eq!(serde_json::to_string(&identifier).unwrap(), json);
+}
+
+#[test]
+fn conversion_errors_point_at_the_failing_members() {
+    let doc = JsonApiDocument::from_str(r#"{
+      "data": {
+        "type": "dog", "id": "1",
+        "attributes": { "name": "fido", "age": "two" },
+        "relationships": {
+          "main_flea": { "data": { "type": "flea", "id": "1" } },
+          "fleas": { "data": [] }
+        }
+      },
+      "included": []
+    }"#).unwrap();
+
+    match Dog::from_jsonapi_document(&doc) {
+        Err(jsonapi::errors::Error::Conversion { ref path, .. }) => {
+            assert_eq!(path, "/attributes/age")
+        }
+        other => panic!("expected a conversion error, got {:?}", other),
+    }
+
+    let errors = Dog::from_jsonapi_document_checked(&doc).unwrap_err();
+    let pointers: Vec<Option<String>> = errors.iter()
+        .map(|error| error.to_jsonapi_error().source.unwrap().pointer)
+        .collect();
+    assert_eq!(pointers, vec![Some("/data/attributes/age".into()),
+                              Some("/data/relationships/main_flea".into())]);
+
+    let error_doc = jsonapi::errors::error_document(&errors);
+    assert!(error_doc.is_valid());
+    assert!(error_doc.errors.unwrap().iter().all(|e| e.status == Some("422".into())));
+}
+
+#[test]
+fn checked_conversion() {
+    let doc = dog_with_fleas().to_jsonapi_document();
+    assert_eq!(Dog::from_jsonapi_document_checked(&doc).unwrap(), dog_with_fleas());
+
+    let doc = JsonApiDocument::from_str(r#"{
+      "data": { "type": "lonely_dog", "id": "1", "attributes": {} }
+    }"#).unwrap();
+    let errors = LonelyDog::from_jsonapi_document_checked(&doc).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].to_string(),
+               "Error converting resource at '/attributes/name': missing field `name`");
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Scalar {
+    id: Option<String>,
+}
+jsonapi_model!(Scalar; "scalar");
+
+impl serde::Serialize for Scalar {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("not an object")
+    }
+}
+
+#[test]
+fn try_variants_fail_instead_of_panicking() {
+    let scalar = Scalar { id: Some("1".into()) };
+    match scalar.try_to_jsonapi_document() {
+        Err(jsonapi::errors::Error::Serialization { ref resource_type, .. }) => {
+            assert_eq!(resource_type, "scalar")
+        }
+        other => panic!("expected a serialization error, got {:?}", other),
+    }
+
+    let mut dog = dog_with_fleas();
+    dog.fleas[1].id = None;
+    match dog.try_to_jsonapi_resource() {
+        Err(jsonapi::errors::Error::MissingId { ref resource_type }) => {
+            assert_eq!(resource_type, "flea")
+        }
+        other => panic!("expected a missing id error, got {:?}", other),
+    }
+    assert!(jsonapi::model::try_vec_to_jsonapi_document(vec![dog]).is_err());
+    assert!(jsonapi::builder::DocumentBuilder::new().try_model(&scalar).is_err());
+    assert!(jsonapi::builder::DocumentBuilder::new().try_models(&[scalar]).is_err());
+    let mut writer = jsonapi::stream::CollectionWriter::new(Vec::new());
+    assert!(writer.write_model(&Scalar { id: Some("2".into()) }).is_err());
+    assert!(Flea { id: None, name: "rick".into() }.try_as_resource_identifier().is_err());
+
+    let doc = dog_with_fleas().try_to_jsonapi_document().unwrap();
+    assert_eq!(doc, dog_with_fleas().to_jsonapi_document());
+    let (resources, included) =
+        try_vec_to_jsonapi_resources(vec![dog_with_fleas()], &Default::default()).unwrap();
+    assert_eq!(resources.len(), 1);
+    assert_eq!(included.unwrap().len(), 3);
+}
@@ -0,0 +1,107 @@
+extern crate jsonapi;
+extern crate serde_json;
+
+use jsonapi::negotiation::*;
+
+const ATOMIC: &str = "https://jsonapi.org/ext/atomic";
+const VERSION: &str = "https://example.com/ext/version";
+const TIMESTAMPS: &str = "https://example.com/profiles/timestamps";
+
+fn negotiator() -> Negotiator {
+    Negotiator::new()
+        .with_extension(ATOMIC)
+        .with_profile(TIMESTAMPS)
+}
+
+#[test]
+fn accepts_plain_content_type() {
+    assert_eq!(negotiator().content_type(Some("application/vnd.api+json")),
+               Ok(MediaType::default()));
+    assert_eq!(negotiator().content_type(None), Ok(MediaType::default()));
+}
+
+#[test]
+fn rejects_content_type_with_other_parameters() {
+    let result = negotiator().content_type(Some("application/vnd.api+json; charset=utf-8"));
+    assert_eq!(result, Err(NegotiationError::UnsupportedMediaType));
+    assert_eq!(result.unwrap_err().status(), 415);
+}
+
+#[test]
+fn rejects_content_type_with_unsupported_extension() {
+    let header = format!("application/vnd.api+json; ext=\"{} {}\"", ATOMIC, VERSION);
+    assert_eq!(negotiator().content_type(Some(&header)),
+               Err(NegotiationError::UnsupportedMediaType));
+}
+
+#[test]
+fn rejects_other_content_types() {
+    assert_eq!(negotiator().content_type(Some("application/json")),
+               Err(NegotiationError::UnsupportedMediaType));
+}
+
+#[test]
+fn negotiates_content_type_extensions_and_profiles() {
+    let header = format!("application/vnd.api+json;ext=\"{}\";profile=\"{} {}\"",
+                         ATOMIC, TIMESTAMPS, "https://example.com/profiles/unknown");
+    let media_type = negotiator().content_type(Some(&header)).unwrap();
+    assert_eq!(media_type.ext, vec![ATOMIC.to_string()]);
+    assert_eq!(media_type.profile, vec![TIMESTAMPS.to_string()]);
+}
+
+#[test]
+fn accepts_missing_or_non_jsonapi_accept() {
+    assert_eq!(negotiator().accept(None), Ok(MediaType::default()));
+    assert_eq!(negotiator().accept(Some("*/*")), Ok(MediaType::default()));
+    assert_eq!(negotiator().accept(Some("application/json, text/html")),
+               Ok(MediaType::default()));
+}
+
+#[test]
+fn not_acceptable_when_all_instances_have_other_parameters() {
+    let result = negotiator().accept(Some("application/vnd.api+json; charset=utf-8, */*"));
+    assert_eq!(result, Err(NegotiationError::NotAcceptable));
+    assert_eq!(result.unwrap_err().status(), 406);
+}
+
+#[test]
+fn not_acceptable_when_all_instances_ask_for_unsupported_extensions() {
+    let header = format!("application/vnd.api+json; ext=\"{}\"", VERSION);
+    assert_eq!(negotiator().accept(Some(&header)), Err(NegotiationError::NotAcceptable));
+}
+
+#[test]
+fn accept_picks_the_best_acceptable_instance() {
+    let header = format!("application/vnd.api+json; charset=utf-8, \
+                          application/vnd.api+json; ext=\"{}\"; q=0.5, \
+                          application/vnd.api+json; ext=\"{}\"; q=0.9, \
+                          application/vnd.api+json; profile=\"{}\"; q=0.8",
+                         VERSION, ATOMIC, TIMESTAMPS);
+    let media_type = negotiator().accept(Some(&header)).unwrap();
+    assert_eq!(media_type.ext, vec![ATOMIC.to_string()]);
+    assert!(media_type.profile.is_empty());
+}
+
+#[test]
+fn media_type_formats_as_content_type() {
+    let media_type = MediaType {
+        ext: vec![ATOMIC.into()],
+        profile: vec![TIMESTAMPS.into()],
+    };
+    assert_eq!(media_type.to_string(),
+               format!("application/vnd.api+json; ext=\"{}\"; profile=\"{}\"",
+                       ATOMIC, TIMESTAMPS));
+    assert_eq!(MediaType::parse(&media_type.to_string()), Some(media_type.clone()));
+    assert_eq!(MediaType::default().to_string(), MEDIA_TYPE);
+
+    let info = serde_json::to_value(media_type.to_jsonapi_info()).unwrap();
+    assert_eq!(info["ext"], serde_json::json!([ATOMIC]));
+    assert_eq!(info["profile"], serde_json::json!([TIMESTAMPS]));
+}
+
+#[test]
+fn negotiation_error_as_jsonapi_error() {
+    let error = NegotiationError::NotAcceptable.to_jsonapi_error();
+    assert_eq!(error.status, Some("406".into()));
+    assert_eq!(error.title, Some("Not Acceptable".into()));
+}
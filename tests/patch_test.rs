@@ -0,0 +1,363 @@
+extern crate jsonapi;
+#[macro_use] extern crate serde_json;
+
+use jsonapi::api::*;
+use jsonapi::errors::Error;
+use jsonapi::patch::JsonPatchOperation;
+use std::str::FromStr;
+
+fn article(json: &str) -> Resource {
+    Resource::from_str(json).unwrap()
+}
+
+fn before() -> Resource {
+    article(r#"{
+      "type": "articles", "id": "1",
+      "attributes": { "title": "Rails is Omakase", "likes": 250, "draft": true },
+      "relationships": {
+        "author": { "data": { "type": "people", "id": "9" } },
+        "editor": { "data": { "type": "people", "id": "2" } },
+        "comments": { "data": [
+          { "type": "comments", "id": "5" },
+          { "type": "comments", "id": "12" },
+          { "type": "comments", "id": "13" }
+        ] }
+      }
+    }"#)
+}
+
+fn after() -> Resource {
+    article(r#"{
+      "type": "articles", "id": "1",
+      "attributes": { "title": "Rails is Omakase", "likes": 251, "published": true },
+      "relationships": {
+        "author": { "data": { "type": "people", "id": "10" } },
+        "comments": { "data": [
+          { "type": "comments", "id": "12" },
+          { "type": "comments", "id": "14" },
+          { "type": "comments", "id": "15" }
+        ] }
+      }
+    }"#)
+}
+
+#[test]
+fn patch_document_has_only_changes() {
+    let doc = before().diff(after()).unwrap().to_patch_document().unwrap();
+    assert!(doc.is_valid());
+
+    assert_eq!(serde_json::to_value(&doc).unwrap(), json!({
+        "data": {
+            "type": "articles",
+            "id": "1",
+            "attributes": { "likes": 251, "draft": null, "published": true },
+            "relationships": {
+                "author": { "data": { "type": "people", "id": "10" } },
+                "editor": { "data": null }
+            }
+        }
+    }));
+}
+
+#[test]
+fn removed_to_many_relationship_is_emptied() {
+    let mut after = before();
+    after.relationships.as_mut().unwrap().remove("comments");
+
+    let doc = before().diff(after).unwrap().to_patch_document().unwrap();
+    match doc.data {
+        Some(PrimaryData::Single(ref resource)) => {
+            assert_eq!(resource.get_relationship("comments").unwrap().data,
+                       Some(IdentifierData::Multiple(vec![])));
+        }
+        _ => panic!("expected a single resource"),
+    }
+}
+
+#[test]
+fn to_many_changes_become_relationship_updates() {
+    let updates = before().diff(after()).unwrap().to_relationship_updates();
+    assert_eq!(updates.len(), 2);
+
+    assert_eq!(updates[0].method(), "DELETE");
+    assert_eq!(updates[0].path(), "/articles/1/relationships/comments");
+    assert_eq!(serde_json::to_value(&updates[0].document).unwrap(), json!({
+        "data": [{ "type": "comments", "id": "5" }, { "type": "comments", "id": "13" }]
+    }));
+
+    assert_eq!(updates[1].method(), "POST");
+    assert_eq!(serde_json::to_value(&updates[1].document).unwrap(), json!({
+        "data": [{ "type": "comments", "id": "14" }, { "type": "comments", "id": "15" }]
+    }));
+}
+
+#[test]
+fn unchanged_resource() {
+    let patchset = before().diff(before()).unwrap();
+    assert!(patchset.to_relationship_updates().is_empty());
+
+    match patchset.to_patch_document().unwrap().data {
+        Some(PrimaryData::Single(ref resource)) => {
+            assert!(resource.attributes.is_empty());
+            assert!(resource.relationships.is_none());
+        }
+        _ => panic!("expected a single resource"),
+    }
+}
+
+#[test]
+fn json_patch_round_trips() {
+    let patchset = before().diff(after()).unwrap();
+    let json_patch = patchset.to_json_patch().unwrap();
+
+    assert_eq!(PatchSet::from_json_patch("articles", "1", &json_patch).unwrap(), patchset);
+    assert_eq!(before().apply_json_patch(&json_patch).unwrap(), after());
+
+    let serialized = serde_json::to_value(&json_patch).unwrap();
+    assert_eq!(serialized[0], json!({ "op": "test", "path": "/attributes/draft", "value": true }));
+    assert_eq!(serialized[1], json!({ "op": "remove", "path": "/attributes/draft" }));
+    let deserialized: Vec<JsonPatchOperation> = serde_json::from_value(serialized).unwrap();
+    assert_eq!(deserialized, json_patch);
+}
+
+#[test]
+fn json_patch_round_trips_without_relationships() {
+    let mut without = after();
+    without.relationships = None;
+
+    for (from, to) in &[(without.clone(), after()), (after(), without)] {
+        let patchset = from.diff(to.clone()).unwrap();
+        let patched = from.apply_json_patch(&patchset.to_json_patch().unwrap()).unwrap();
+        assert_eq!(patched, *to);
+        assert_eq!(patched, from.clone().patch(patchset).unwrap());
+    }
+}
+
+#[test]
+fn json_patch_paths() {
+    let mut after = before();
+    after.attributes.insert("a/b~c".into(), json!(1));
+    after.relationships.as_mut().unwrap().remove("editor");
+
+    let json_patch = before().diff(after.clone()).unwrap().to_json_patch().unwrap();
+    assert_eq!(json_patch, vec![
+        JsonPatchOperation::Add { path: "/attributes/a~1b~0c".into(), value: json!(1) },
+        JsonPatchOperation::Test {
+            path: "/relationships/editor/data".into(),
+            value: json!({ "type": "people", "id": "2" }),
+        },
+        JsonPatchOperation::Remove { path: "/relationships/editor".into() },
+    ]);
+    assert_eq!(before().apply_json_patch(&json_patch).unwrap(), after);
+}
+
+#[test]
+fn applies_json_patch() {
+    let operations: Vec<JsonPatchOperation> = serde_json::from_value(json!([
+        { "op": "test", "path": "/attributes/likes", "value": 250 },
+        { "op": "copy", "from": "/attributes/likes", "path": "/attributes/views" },
+        { "op": "move", "from": "/attributes/title", "path": "/attributes/name" },
+        { "op": "add", "path": "/relationships/comments/data/0",
+          "value": { "type": "comments", "id": "1" } },
+        { "op": "remove", "path": "/relationships/comments/data/3" },
+        { "op": "replace", "path": "/relationships/author/data", "value": null }
+    ])).unwrap();
+
+    let patched = before().apply_json_patch(&operations).unwrap();
+    assert_eq!(patched.get_attribute("views"), Some(&json!(250)));
+    assert_eq!(patched.get_attribute("name"), Some(&json!("Rails is Omakase")));
+    assert_eq!(patched.get_attribute("title"), None);
+    assert_eq!(patched.get_relationship("comments").unwrap().as_ids(),
+               Ok(Some(vec![&"1".to_string(), &"5".to_string(), &"12".to_string()])));
+    assert_eq!(patched.get_relationship("author").unwrap().data, Some(IdentifierData::None));
+}
+
+#[test]
+fn json_patch_errors() {
+    let apply = |operation: serde_json::Value| {
+        let operations: Vec<JsonPatchOperation> = serde_json::from_value(json!([operation]))
+            .unwrap();
+        before().apply_json_patch(&operations).unwrap_err()
+    };
+
+    assert_eq!(apply(json!({ "op": "test", "path": "/attributes/likes", "value": 1 })),
+               JsonPatchError::TestFailed("/attributes/likes".into()));
+    assert_eq!(apply(json!({ "op": "remove", "path": "/attributes/body" })),
+               JsonPatchError::PathNotFound("/attributes/body".into()));
+    assert_eq!(apply(json!({ "op": "add", "path": "/attributes/body/text", "value": 1 })),
+               JsonPatchError::PathNotFound("/attributes/body".into()));
+    assert_eq!(apply(json!({ "op": "remove", "path": "/relationships/comments/data/3" })),
+               JsonPatchError::PathNotFound("/relationships/comments/data/3".into()));
+    assert_eq!(apply(json!({ "op": "replace", "path": "attributes", "value": 1 })),
+               JsonPatchError::InvalidPointer("attributes".into()));
+    assert!(matches!(apply(json!({ "op": "replace", "path": "/type", "value": 1 })),
+                     JsonPatchError::InvalidResource(_)));
+
+    let copy = vec![JsonPatchOperation::Copy { from: "/id".into(), path: "/lid".into() }];
+    assert_eq!(PatchSet::from_json_patch("articles", "1", &copy),
+               Err(JsonPatchError::Unsupported("/lid".into())));
+}
+
+#[test]
+fn json_patch_resolves_last_element_removals() {
+    let mut patchset = PatchSet::new_for(&before());
+    patchset.push(Patch::relationship(PatchOperation::Add, "comments/-", json!(null),
+                                      json!({ "type": "comments", "id": "1" })));
+    let undo = patchset.invert();
+
+    assert_eq!(undo.to_json_patch(),
+               Err(JsonPatchError::Unsupported("/relationships/comments/data/-".into())));
+    let patched = before().patch(patchset).unwrap();
+    let json_patch = undo.to_json_patch_for(&patched).unwrap();
+    assert_eq!(json_patch[1], JsonPatchOperation::Remove {
+        path: "/relationships/comments/data/3".into(),
+    });
+    assert_eq!(patched.apply_json_patch(&json_patch).unwrap(), before());
+}
+
+#[test]
+fn inverted_json_patch_round_trips() {
+    let mut before = before();
+    before.set_attribute("tags", vec!["rails", "ruby"]).unwrap();
+    let mut after = after();
+    after.set_attribute("tags", vec!["ruby", "opinion", "mvc"]).unwrap();
+
+    let patchset = before.diff_deep(after.clone()).unwrap();
+    let json_patch = patchset.invert().to_json_patch_for(&after).unwrap();
+    assert_eq!(after.apply_json_patch(&json_patch).unwrap(), before);
+}
+
+#[test]
+fn json_patch_tests_compare_numbers_by_value() {
+    let operations = vec![JsonPatchOperation::Test {
+        path: "/attributes/likes".into(),
+        value: json!(250.0),
+    }];
+    assert_eq!(before().apply_json_patch(&operations).unwrap(), before());
+}
+
+#[test]
+fn deep_patches_in_json_patch() {
+    let mut before = before();
+    before.set_attribute("settings.theme", "dark").unwrap();
+    let mut after = before.clone();
+    after.set_attribute("settings.theme", "light").unwrap();
+
+    let patchset = before.diff_deep(after.clone()).unwrap();
+    let json_patch = patchset.to_json_patch().unwrap();
+    assert_eq!(json_patch[1], JsonPatchOperation::Replace {
+        path: "/attributes/settings/theme".into(),
+        value: json!("light"),
+    });
+    assert_eq!(PatchSet::from_json_patch("articles", "1", &json_patch).unwrap(), patchset);
+    assert_eq!(before.apply_json_patch(&json_patch).unwrap(), after);
+
+}
+
+#[test]
+fn patch_document_needs_whole_attribute_values() {
+    let mut before = before();
+    before.set_attribute("settings.theme", "dark").unwrap();
+    let mut after = before.clone();
+    after.set_attribute("settings.theme", "light").unwrap();
+    after.attributes.insert("likes".into(), json!(251));
+
+    match before.diff_deep(after.clone()).unwrap().to_patch_document() {
+        Err(Error::InvalidPatch { ref subject, .. }) => assert_eq!(subject, "settings/theme"),
+        other => panic!("expected a patch error, got {:?}", other),
+    }
+
+    let doc = before.diff(after).unwrap().to_patch_document().unwrap();
+    assert_eq!(serde_json::to_value(&doc).unwrap()["data"]["attributes"], json!({
+        "likes": 251, "settings": { "theme": "light" }
+    }));
+}
+
+#[test]
+fn patch_document_refuses_invalid_relationship_data() {
+    let mut patchset = PatchSet::try_new_for(&before()).unwrap();
+    patchset.push(Patch::relationship(PatchOperation::Replace, "author",
+                                      json!({ "type": "people", "id": "9" }), json!(42)));
+
+    match patchset.to_patch_document() {
+        Err(Error::InvalidPatch { ref subject, .. }) => assert_eq!(subject, "author"),
+        other => panic!("expected a patch error, got {:?}", other),
+    }
+}
+
+#[test]
+fn merge_patch_round_trips() {
+    let merge_patch = before().diff_merge_patch(&after()).unwrap();
+    assert_eq!(merge_patch, json!({
+        "attributes": { "likes": 251, "draft": null, "published": true },
+        "relationships": {
+            "author": { "data": { "id": "10" } },
+            "editor": null,
+            "comments": { "data": [
+                { "type": "comments", "id": "12" },
+                { "type": "comments", "id": "14" },
+                { "type": "comments", "id": "15" }
+            ] }
+        }
+    }));
+
+    let (patched, patchset) = before().apply_merge_patch(&merge_patch).unwrap();
+    assert_eq!(patched, after());
+    assert_eq!(patchset, before().diff_deep(after()).unwrap());
+    assert_eq!(after().diff_merge_patch(&after()).unwrap(), json!({}));
+}
+
+#[test]
+fn merge_patch_agrees_with_its_patchset() {
+    for merge_patch in &[
+        json!({ "relationships": null }),
+        json!({ "attributes": { "title": null }, "relationships": { "editor": null } }),
+        json!({ "relationships": { "tags": { "data": [{ "type": "tags", "id": "1" }] } } }),
+    ] {
+        let (patched, patchset) = before().apply_merge_patch(merge_patch).unwrap();
+        assert_eq!(before().patch(patchset).unwrap(), patched);
+    }
+
+    let (patched, _) = before().apply_merge_patch(&json!({ "relationships": null })).unwrap();
+    assert!(patched.relationships.is_none());
+}
+
+#[test]
+fn merge_patch_rejects_changes_patches_can_not_express() {
+    let mut linked = before();
+    linked.relationships.as_mut().unwrap().insert("related".into(), serde_json::from_value(
+        json!({ "links": { "related": "http://example.com/articles/1/related" } })).unwrap());
+
+    for &(ref resource, ref merge_patch, path) in &[
+        (before(), json!({ "meta": { "views": 10 } }), "/meta"),
+        (before(), json!({ "relationships": { "editor": { "data": null } } }),
+         "/relationships/editor/data"),
+        (before(), json!({ "relationships": { "editor": { "links": {} } } }),
+         "/relationships/editor/links"),
+        (linked.clone(), json!({ "relationships": { "related": null } }), "/relationships/related"),
+        (linked, json!({ "relationships": null }), "/relationships/related"),
+    ] {
+        assert_eq!(resource.apply_merge_patch(merge_patch).unwrap_err(),
+                   JsonPatchError::Unsupported(path.into()));
+    }
+}
+
+#[test]
+fn merge_patch_errors() {
+    assert_eq!(before().apply_merge_patch(&json!({ "type": "people" })).unwrap_err(),
+               JsonPatchError::Unsupported("/type".into()));
+    assert!(matches!(before().apply_merge_patch(&json!([])),
+                     Err(JsonPatchError::InvalidResource(_))));
+    assert!(matches!(before().apply_merge_patch(&json!({ "attributes": 1 })),
+                     Err(JsonPatchError::InvalidResource(_))));
+
+    let mut unsaved = before();
+    unsaved.id = None;
+    assert_eq!(unsaved.apply_merge_patch(&json!({ "attributes": { "likes": 1 } })).unwrap_err(),
+               JsonPatchError::InvalidResource("resource has no id".into()));
+
+    let mut person = before();
+    person._type = "people".into();
+    assert_eq!(before().diff_merge_patch(&person),
+               Err(DiffPatchError::IncompatibleTypes("articles".into(), "people".into())));
+}
@@ -0,0 +1,169 @@
+#[macro_use] extern crate jsonapi;
+#[macro_use] extern crate serde_derive;
+#[macro_use] extern crate serde_json;
+
+use jsonapi::errors::Error;
+use jsonapi::model::*;
+use jsonapi::stream::{to_writer, CollectionWriter, DocumentItem, DocumentReader};
+use std::fs::File;
+use std::str::FromStr;
+
+mod helper;
+use helper::read_json_file;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Article {
+    id: Option<String>,
+    title: String,
+    author: Person,
+}
+jsonapi_model!(Article; "articles"; has one author);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Person {
+    id: Option<String>,
+    name: String,
+}
+jsonapi_model!(Person; "people");
+
+fn articles(count: usize) -> Vec<Article> {
+    (0..count)
+        .map(|i| Article {
+            id: Some(i.to_string()),
+            title: format!("Article {}", i),
+            author: Person { id: Some((i % 2).to_string()), name: format!("Author {}", i % 2) },
+        })
+        .collect()
+}
+
+#[test]
+fn matches_vec_to_jsonapi_document() {
+    let out = to_writer(Vec::new(), articles(5)).unwrap();
+    let doc: JsonApiDocument = serde_json::from_slice(&out).unwrap();
+
+    assert!(doc.is_valid());
+    let expected = vec_to_jsonapi_document(articles(5));
+    assert_eq!(doc.data, expected.data);
+
+    let included = doc.included.unwrap();
+    assert_eq!(included.len(), 2);
+    let mut names: Vec<&serde_json::Value> =
+        included.iter().map(|r| r.get_attribute("name").unwrap()).collect();
+    names.sort_by_key(|name| name.to_string());
+    assert_eq!(names, vec![&json!("Author 0"), &json!("Author 1")]);
+}
+
+#[test]
+fn empty_collection() {
+    let out = to_writer(Vec::new(), Vec::<Person>::new()).unwrap();
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&out).unwrap(), json!({ "data": [] }));
+}
+
+#[test]
+fn top_level_members() {
+    let mut meta = Meta::new();
+    meta.insert("total".into(), json!(1));
+    let mut links = Links::new();
+    links.insert("self".into(), "http://example.com/people".into());
+
+    let mut writer = CollectionWriter::new(Vec::new()).with_meta(meta).with_links(links);
+    writer.write_model(&Person { id: Some("1".into()), name: "Dan".into() }).unwrap();
+    let out = writer.finish().unwrap();
+
+    assert_eq!(serde_json::from_slice::<serde_json::Value>(&out).unwrap(), json!({
+        "data": [{ "type": "people", "id": "1", "attributes": { "name": "Dan" } }],
+        "links": { "self": "http://example.com/people" },
+        "meta": { "total": 1 }
+    }));
+}
+
+#[test]
+fn leaves_primary_resources_out_of_included() {
+    let mut writer = CollectionWriter::new(Vec::new());
+    let author = Person { id: Some("0".into()), name: "Author 0".into() };
+
+    writer.write_model(&articles(1).remove(0)).unwrap();
+    writer.write_model(&author).unwrap();
+    writer.include(author.to_jsonapi_resource().0);
+    let out = writer.finish().unwrap();
+
+    let doc: JsonApiDocument = serde_json::from_slice(&out).unwrap();
+    assert!(doc.included.is_none());
+    assert!(doc.is_valid());
+}
+
+#[test]
+fn reads_what_from_str_reads() {
+    for name in &["data/collection.json", "data/compound_document.json", "data/errors.json",
+                  "data/resource_001.json", "data/links_001.json"] {
+        let expected = JsonApiDocument::from_str(&read_json_file(name)).unwrap();
+
+        let mut reader = DocumentReader::new(File::open(name).unwrap());
+        let mut data = Vec::new();
+        let mut included = Vec::new();
+        for item in reader.by_ref() {
+            match item.unwrap() {
+                DocumentItem::Data(resource) => data.push(resource),
+                DocumentItem::Included(resource) => included.push(resource),
+            }
+        }
+        let mut doc = reader.into_document().unwrap();
+
+        doc.data = match expected.data {
+            Some(PrimaryData::Single(_)) => Some(PrimaryData::Single(Box::new(data.remove(0)))),
+            Some(PrimaryData::Multiple(_)) => Some(PrimaryData::Multiple(data)),
+            _ => doc.data,
+        };
+        if expected.included.is_some() {
+            doc.included = Some(included);
+        }
+        assert_eq!(doc, expected, "{}", name);
+    }
+}
+
+#[test]
+fn reads_large_collections_incrementally() {
+    let out = to_writer(Vec::new(), articles(1000)).unwrap();
+
+    let mut reader = DocumentReader::new(&out[..]);
+    let first = reader.next().unwrap().unwrap();
+    assert_eq!(first.resource().id, Some("0".into()));
+
+    let count = reader.filter(|item| matches!(item, Ok(DocumentItem::Data(_)))).count();
+    assert_eq!(count, 999);
+}
+
+#[test]
+fn null_data() {
+    let doc = DocumentReader::new(&br#"{ "data": null, "meta": { "a": "\"}" } }"#[..])
+        .into_document()
+        .unwrap();
+    assert_eq!(doc.data, Some(PrimaryData::None));
+    assert_eq!(doc.meta.unwrap()["a"], json!("\"}"));
+}
+
+#[test]
+fn reports_malformed_documents() {
+    let mut reader = DocumentReader::new(&br#"{ "data": [{ "type": "people", "id": "1" } {"#[..]);
+    assert!(reader.next().unwrap().is_ok());
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+
+    let mut reader = DocumentReader::new(&br#"{ "data": [{ "type": "people""#[..]);
+    assert!(reader.next().unwrap().is_err());
+}
+
+#[test]
+fn rejects_trailing_content_and_non_json_whitespace() {
+    let reader = DocumentReader::new(&b"{ \"meta\": {} }\n"[..]);
+    assert!(reader.into_document().is_ok());
+
+    let reader = DocumentReader::new(&b"{ \"meta\": {} } {}"[..]);
+    match reader.into_document() {
+        Err(Error::Parse { line, column, .. }) => assert_eq!((line, column), (1, 16)),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let reader = DocumentReader::new(&b"{\x0c\"meta\": {} }"[..]);
+    assert!(reader.into_document().is_err());
+}